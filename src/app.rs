@@ -1,6 +1,8 @@
 use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashSet},
     sync::{Arc, RwLock, mpsc},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use itsuki::zero_indexed_enum;
@@ -12,7 +14,17 @@ use ratatui::{
 };
 use tui_input::{Input, backend::crossterm::EventHandler};
 
-use crate::{event::AppEvent, interpreter::Interpreter, ui};
+use crate::{
+    escape,
+    event::AppEvent,
+    interpreter::{
+        CellAccess, Dump, Interpreter, RunOutcome, RuntimeError, StepOutcome, breakpoint_markers,
+        condition::Condition, config::Config, coverage::Coverage, dialect::Dialect,
+        profile::LoopCount,
+    },
+    ui,
+    widget::memory::{CellFormat, scroll_offset},
+};
 
 #[zero_indexed_enum]
 enum State {
@@ -20,12 +32,31 @@ enum State {
     Stop,
     Play,
     AutoPlay,
+    FastRun,
+    Failed,
+    Invalid,
 }
 
 #[zero_indexed_enum]
 enum EditState {
     None,
     EditInput,
+    EnterInputLine,
+    EditWatchValue,
+    EditBreakpointCondition,
+    EditBreakCondition,
+}
+
+impl EditState {
+    /// Whether the state edits a single line in `App::prompt_input`.
+    pub fn prompt(&self) -> bool {
+        matches!(
+            self,
+            EditState::EditWatchValue
+                | EditState::EditBreakpointCondition
+                | EditState::EditBreakCondition
+        )
+    }
 }
 
 #[zero_indexed_enum]
@@ -37,6 +68,8 @@ enum SelectItem {
     Reset,
     Start,
     Pause,
+    Run,
+    Back,
     Step,
     Speed,
 }
@@ -55,10 +88,13 @@ impl SelectItem {
     fn not_allowed_items(state: State) -> &'static [SelectItem] {
         use SelectItem::*;
         match state {
-            State::Default => &[Reset, Pause],
-            State::Stop => &[Start, Pause, Step, Speed],
+            State::Default => &[Reset, Pause, Back],
+            State::Stop => &[Start, Pause, Run, Step, Speed],
             State::Play => &[Pause],
             State::AutoPlay => &[Start],
+            State::FastRun => &[Start, Run, Back, Step, Speed],
+            State::Failed => &[Start, Pause, Run, Step, Speed],
+            State::Invalid => &[Start, Pause, Run, Back, Step, Speed],
         }
     }
 }
@@ -84,32 +120,199 @@ impl Speed {
     }
 }
 
+/// Annotation of the commands in the Source panel.
+#[zero_indexed_enum]
+enum SourceView {
+    Plain,
+    Heatmap,
+    Coverage,
+}
+
+/// Arrangement of the cells in the Memory panel.
+#[zero_indexed_enum]
+enum MemoryLayout {
+    Row,
+    Grid16,
+    Grid32,
+}
+
+impl MemoryLayout {
+    /// Returns the number of cells per row of the grid.
+    pub fn columns(&self) -> Option<usize> {
+        match self {
+            MemoryLayout::Row => None,
+            MemoryLayout::Grid16 => Some(16),
+            MemoryLayout::Grid32 => Some(32),
+        }
+    }
+}
+
+/// Order of the loops in the hot loops table.
+#[zero_indexed_enum]
+enum LoopOrder {
+    Steps,
+    Iterations,
+    Position,
+}
+
+impl LoopOrder {
+    pub fn label(&self) -> &'static str {
+        match self {
+            LoopOrder::Steps => "steps",
+            LoopOrder::Iterations => "iterations",
+            LoopOrder::Position => "position",
+        }
+    }
+
+    pub fn sort(&self, loops: &mut [LoopCount]) {
+        match self {
+            LoopOrder::Steps => loops.sort_by_key(|l| std::cmp::Reverse(l.steps)),
+            LoopOrder::Iterations => loops.sort_by_key(|l| std::cmp::Reverse(l.iterations)),
+            LoopOrder::Position => loops.sort_by_key(|l| l.start),
+        }
+    }
+}
+
+/// Conditions on a tape cell that pause auto play when met.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Watch {
+    pub read: bool,
+    pub write: bool,
+    pub value: Option<i64>,
+}
+
+impl Watch {
+    fn is_empty(&self) -> bool {
+        !self.read && !self.write && self.value.is_none()
+    }
+
+    fn triggered_by(&self, access: CellAccess, value: i64) -> bool {
+        match access {
+            CellAccess::Read(_) => self.read,
+            CellAccess::Write(_) => self.write || self.value == Some(value),
+        }
+    }
+}
+
+/// Input given to the program on start.
+pub struct AppInput {
+    pub bytes: Vec<u8>,
+    /// File the input was read from, if any.
+    pub file: Option<String>,
+    /// Whether the input is the text after `!` in the source, which is read again on Reset.
+    pub from_source: bool,
+    /// Whether `,` waits for a line to be entered when the input is exhausted.
+    pub interactive: bool,
+}
+
+/// Maximum time spent executing between redraws in fast runs.
+const FAST_RUN_SLICE: Duration = Duration::from_millis(15);
+/// Number of instructions executed between checks of `FAST_RUN_SLICE`.
+const FAST_RUN_CHUNK: usize = 10_000;
+/// Rows of cells shown in the grid layout of the Memory panel until resized.
+const DEFAULT_MEMORY_ROWS: usize = 4;
+
 pub struct App {
     pub state: State,
     pub edit_state: EditState,
     pub selected: SelectItem,
+    pub source_file: String,
     pub source: Vec<String>,
     pub source_scroll_offset: usize,
+    pub source_view_height: Cell<usize>,
+    pub source_cursor: (usize, usize),
+    pub source_view: SourceView,
+    pub loop_order: LoopOrder,
+    pub breakpoints: BTreeMap<(usize, usize), Option<Condition>>,
+    pub break_condition: Option<Condition>,
+    pub memory_cursor: isize,
+    pub memory_offset: Cell<isize>, // address of the first cell in the Memory panel
+    pub memory_view_width: Cell<usize>, // cells per row
+    pub memory_view_height: Cell<usize>, // rows of cells
+    pub memory_layout: MemoryLayout,
+    pub memory_rows: usize, // rows requested for the grid layout
+    pub cell_format: CellFormat,
+    pub ascii_row: bool,
+    pub watches: BTreeMap<isize, Watch>,
+    pub prompt_input: Input,
+    pub prompt_error: Option<String>,
     pub input_input: Input,
+    pub input_bytes: Vec<u8>, // `input_input` with the escape sequences decoded
+    pub input_file: Option<String>,
+    input_from_source: bool,
+    pub interactive_input: bool,
+    pub line_input: Input, // line entered while the program waits for input
+    input_resume: State,   // state to return to after the line is entered
+    pub output_raw: bool,
+    pub dumps: Vec<Dump>, // snapshots taken by `#`
+    coverage: Coverage,   // coverage of the previous runs since the source was loaded
     pub interpreter: Interpreter,
+    pub config: Config,
+    pub dialect: Dialect,
+    pub runtime_error: Option<RuntimeError>,
     pub speed: Arc<RwLock<Speed>>,
     pub debug: bool,
     quit: bool,
 }
 
 impl App {
-    pub fn new(source: String, speed: Arc<RwLock<Speed>>, debug: bool) -> App {
-        let input_input = Input::default();
-        let interpreter = Interpreter::new(&source, input_input.value());
+    pub fn new(
+        source_file: String,
+        source: String,
+        input: AppInput,
+        config: Config,
+        dialect: Dialect,
+        speed: Arc<RwLock<Speed>>,
+        debug: bool,
+    ) -> App {
+        let input_input = Input::new(escape::escape(&input.bytes));
+        let input_bytes = input.bytes;
+        let interpreter =
+            new_interpreter(&source, &input_bytes, config, &dialect, input.interactive);
+        let state = initial_state(&interpreter);
+        let breakpoints = breakpoint_markers(&source, &dialect)
+            .into_iter()
+            .map(|p| (p, None))
+            .collect();
         let source = source.lines().map(|s| s.to_string()).collect();
         App {
-            state: State::Default,
+            state,
             edit_state: EditState::None,
             selected: SelectItem::Source,
+            source_file,
             source,
             source_scroll_offset: 0,
+            source_view_height: Cell::new(0),
+            source_cursor: (0, 0),
+            source_view: SourceView::Plain,
+            loop_order: LoopOrder::Steps,
+            breakpoints,
+            break_condition: None,
+            memory_cursor: 0,
+            memory_offset: Cell::new(0),
+            memory_view_width: Cell::new(0),
+            memory_view_height: Cell::new(1),
+            memory_layout: MemoryLayout::Row,
+            memory_rows: DEFAULT_MEMORY_ROWS,
+            cell_format: CellFormat::Hex,
+            ascii_row: false,
+            watches: BTreeMap::new(),
+            prompt_input: Input::default(),
+            prompt_error: None,
             input_input,
+            input_bytes,
+            input_file: input.file,
+            input_from_source: input.from_source,
+            interactive_input: input.interactive,
+            line_input: Input::default(),
+            input_resume: State::Play,
+            output_raw: false,
+            dumps: Vec::new(),
+            coverage: Coverage::default(),
             interpreter,
+            config,
+            dialect,
+            runtime_error: None,
             speed,
             debug,
             quit: false,
@@ -124,16 +327,28 @@ impl App {
         while !self.quit {
             terminal.draw(|f| ui::render(f, self))?;
 
-            match rx.recv().unwrap() {
-                AppEvent::Key(key) => {
+            // fast runs continue between events instead of waiting for ticks
+            let event = match self.state {
+                State::FastRun => match rx.try_recv() {
+                    Err(mpsc::TryRecvError::Empty) => None,
+                    event => Some(event.unwrap()),
+                },
+                _ => Some(rx.recv().unwrap()),
+            };
+
+            match event {
+                Some(AppEvent::Key(key)) => {
                     self.handle_key_event(key);
                 }
-                AppEvent::Resize(w, h) => {
+                Some(AppEvent::Resize(w, h)) => {
                     self.handle_resize(w, h);
                 }
-                AppEvent::Tick => {
+                Some(AppEvent::Tick) => {
                     self.handle_tick();
                 }
+                None => {
+                    self.run_fast();
+                }
             }
         }
         Ok(())
@@ -147,13 +362,73 @@ impl App {
                 key_code_char!('c', Ctrl) => {
                     self.quit = true;
                 }
+                key_code!(KeyCode::Esc) => match escape::unescape(self.input_input.value()) {
+                    Ok(bytes) => {
+                        self.edit_state = EditState::None;
+                        self.prompt_error = None;
+                        if bytes != self.input_bytes {
+                            self.input_file = None;
+                            self.input_from_source = false;
+                        }
+                        self.interpreter.set_input(bytes.clone());
+                        self.input_bytes = bytes;
+                    }
+                    Err(e) => {
+                        self.prompt_error = Some(e);
+                    }
+                },
+                _ => {
+                    self.input_input.handle_event(&Event::Key(key));
+                }
+            }
+            return;
+        }
+
+        if self.edit_state == EditState::EnterInputLine {
+            match key {
+                key_code_char!('c', Ctrl) => {
+                    self.quit = true;
+                }
+                key_code_char!('d', Ctrl) => {
+                    self.interpreter.close_input();
+                    self.resume_after_input();
+                }
                 key_code!(KeyCode::Esc) => {
                     self.edit_state = EditState::None;
-                    self.interpreter
-                        .set_input(self.input_input.value().to_owned());
+                    self.prompt_error = None;
+                    self.selected = SelectItem::Start;
                 }
+                key_code!(KeyCode::Enter) => match escape::unescape(self.line_input.value()) {
+                    Ok(mut bytes) => {
+                        bytes.push(b'\n');
+                        self.interpreter.push_input(&bytes);
+                        self.resume_after_input();
+                    }
+                    Err(e) => {
+                        self.prompt_error = Some(e);
+                    }
+                },
                 _ => {
-                    self.input_input.handle_event(&Event::Key(key));
+                    self.line_input.handle_event(&Event::Key(key));
+                }
+            }
+            return;
+        }
+
+        if self.edit_state.prompt() {
+            match key {
+                key_code_char!('c', Ctrl) => {
+                    self.quit = true;
+                }
+                key_code!(KeyCode::Esc) => {
+                    self.edit_state = EditState::None;
+                    self.prompt_error = None;
+                }
+                key_code!(KeyCode::Enter) => {
+                    self.submit_prompt();
+                }
+                _ => {
+                    self.prompt_input.handle_event(&Event::Key(key));
                 }
             }
             return;
@@ -171,9 +446,10 @@ impl App {
             }
             key_code_char!('j') => match self.selected {
                 SelectItem::Source => {
-                    if self.source_scroll_offset < self.source.len() - 1 {
-                        self.source_scroll_offset = self.source_scroll_offset.saturating_add(1);
-                    }
+                    self.move_source_cursor_line(1);
+                }
+                SelectItem::Memory if self.memory_layout != MemoryLayout::Row => {
+                    self.move_memory_cursor(self.memory_view_width.get() as isize);
                 }
                 SelectItem::Speed => {
                     let mut s = self.speed.write().unwrap();
@@ -183,9 +459,10 @@ impl App {
             },
             key_code_char!('k') => match self.selected {
                 SelectItem::Source => {
-                    if self.source_scroll_offset > 0 {
-                        self.source_scroll_offset = self.source_scroll_offset.saturating_sub(1);
-                    }
+                    self.move_source_cursor_line(-1);
+                }
+                SelectItem::Memory if self.memory_layout != MemoryLayout::Row => {
+                    self.move_memory_cursor(-(self.memory_view_width.get() as isize));
                 }
                 SelectItem::Speed => {
                     let mut s = self.speed.write().unwrap();
@@ -193,47 +470,179 @@ impl App {
                 }
                 _ => {}
             },
+            key_code_char!('l') => match self.selected {
+                SelectItem::Source => {
+                    let cursor = self.source_cursor;
+                    let next = self.interpreter.command_positions().find(|p| *p > cursor);
+                    self.move_source_cursor(next);
+                }
+                SelectItem::Memory => {
+                    self.move_memory_cursor(1);
+                }
+                _ => {}
+            },
+            key_code_char!('h') => match self.selected {
+                SelectItem::Source => {
+                    let cursor = self.source_cursor;
+                    let prev = self
+                        .interpreter
+                        .command_positions()
+                        .filter(|p| *p < cursor)
+                        .last();
+                    self.move_source_cursor(prev);
+                }
+                SelectItem::Memory => {
+                    self.move_memory_cursor(-1);
+                }
+                _ => {}
+            },
+            key_code_char!('f') => {
+                if self.selected == SelectItem::Memory {
+                    self.cell_format = self.cell_format.next();
+                }
+            }
+            key_code_char!('a') => {
+                if self.selected == SelectItem::Memory {
+                    self.ascii_row = !self.ascii_row;
+                }
+            }
+            key_code_char!('g') => {
+                if self.selected == SelectItem::Memory {
+                    self.memory_layout = self.memory_layout.next();
+                }
+            }
+            key_code_char!('+') => {
+                if self.selected == SelectItem::Memory && self.memory_layout != MemoryLayout::Row {
+                    self.memory_rows = self.memory_view_height.get() + 1;
+                }
+            }
+            key_code_char!('-') => {
+                if self.selected == SelectItem::Memory && self.memory_layout != MemoryLayout::Row {
+                    self.memory_rows = self.memory_view_height.get().saturating_sub(1).max(1);
+                }
+            }
+            key_code_char!('w') => {
+                if self.selected == SelectItem::Memory {
+                    self.update_watch(|w| w.write = !w.write);
+                }
+            }
+            key_code_char!('r') => match self.selected {
+                SelectItem::Memory => {
+                    self.update_watch(|w| w.read = !w.read);
+                }
+                SelectItem::Output => {
+                    self.output_raw = !self.output_raw;
+                }
+                _ => {}
+            },
+            key_code_char!('v') => {
+                if self.selected == SelectItem::Memory {
+                    let value = self.watches.get(&self.memory_cursor).and_then(|w| w.value);
+                    let value = value.map(|v| v.to_string()).unwrap_or_default();
+                    self.open_prompt(EditState::EditWatchValue, value);
+                }
+            }
+            key_code_char!('c') => {
+                let cursor = self.source_cursor;
+                if self.selected == SelectItem::Source
+                    && self.interpreter.command_positions().any(|p| p == cursor)
+                {
+                    let condition = self.breakpoints.get(&cursor).cloned().flatten();
+                    let condition = condition.map(|c| c.to_string()).unwrap_or_default();
+                    self.open_prompt(EditState::EditBreakpointCondition, condition);
+                }
+            }
+            key_code_char!('C') => {
+                if self.selected == SelectItem::Source {
+                    let condition = self.break_condition.as_ref();
+                    let condition = condition.map(|c| c.to_string()).unwrap_or_default();
+                    self.open_prompt(EditState::EditBreakCondition, condition);
+                }
+            }
+            key_code_char!('b') => {
+                if self.selected == SelectItem::Source {
+                    self.toggle_breakpoint();
+                }
+            }
+            key_code_char!('p') => {
+                if self.selected == SelectItem::Source {
+                    self.source_view = self.source_view.next();
+                }
+            }
+            key_code_char!('s') => {
+                if self.selected == SelectItem::Source && self.source_view == SourceView::Heatmap {
+                    self.loop_order = self.loop_order.next();
+                }
+            }
+            key_code_char!('B') => {
+                if let State::Play | State::AutoPlay | State::Stop | State::Failed = self.state {
+                    self.run_back_to_breakpoint();
+                }
+            }
             key_code_char!('e') => {
                 if let (State::Default, SelectItem::Input) = (self.state, self.selected) {
                     self.edit_state = EditState::EditInput;
+                    self.coverage = self.coverage();
                     self.reset_interpreter();
                 }
             }
             key_code!(KeyCode::Enter) => match self.selected {
                 SelectItem::Reset => {
-                    self.state = State::Default;
-                    self.selected = SelectItem::Start;
+                    self.coverage = self.coverage();
+                    self.reload_source();
                     self.reset_interpreter();
+                    self.sync_breakpoints();
+                    self.state = initial_state(&self.interpreter);
+                    self.selected = match self.state {
+                        State::Invalid => SelectItem::Reset,
+                        _ => SelectItem::Start,
+                    };
                 }
-                SelectItem::Start => {
-                    if !self.interpreter.end() {
+                SelectItem::Start if !self.interpreter.end() => {
+                    if self.breakpoint_at_start() {
+                        self.state = State::Play;
+                    } else {
                         self.state = State::AutoPlay;
                         self.selected = SelectItem::Pause;
                     }
                 }
-                SelectItem::Pause => {
-                    if !self.interpreter.end() {
+                SelectItem::Pause if !self.interpreter.end() => {
+                    self.state = State::Play;
+                    self.selected = SelectItem::Start;
+                }
+                SelectItem::Run if !self.interpreter.end() => {
+                    if self.breakpoint_at_start() {
                         self.state = State::Play;
                         self.selected = SelectItem::Start;
+                    } else {
+                        self.state = State::FastRun;
+                        self.selected = SelectItem::Pause;
                     }
                 }
+                SelectItem::Back => {
+                    self.step_back_interpreter();
+                }
                 SelectItem::Step => {
                     if self.interpreter.end() {
                         self.state = State::Stop;
                         self.selected = SelectItem::Reset;
                     } else {
-                        self.interpreter.step();
                         self.state = State::Play;
+                        self.step_interpreter();
                     }
                 }
                 _ => {}
             },
             key_code_char!(' ') => {
+                if self.breakpoint_at_start() {
+                    self.state = State::Play;
+                    return;
+                }
                 match self.state {
                     State::Default | State::Play => {
                         self.state = State::AutoPlay;
                     }
-                    State::AutoPlay => {
+                    State::AutoPlay | State::FastRun => {
                         self.state = State::Play;
                     }
                     _ => {}
@@ -259,17 +668,385 @@ impl App {
         if self.state == State::AutoPlay {
             if self.interpreter.end() {
                 self.state = State::Stop;
-                if let Start | Pause | Step | Speed = self.selected {
+                if let Start | Pause | Run | Step | Speed = self.selected {
                     self.selected = Reset;
                 }
             } else {
-                self.interpreter.step();
+                self.step_interpreter();
+                if self.state == State::AutoPlay && self.should_pause() {
+                    self.state = State::Play;
+                    if self.selected == Pause {
+                        self.selected = Start;
+                    }
+                }
             }
         }
     }
 
+    fn run_fast(&mut self) {
+        use SelectItem::*;
+        let started = Instant::now();
+        let stops: Vec<_> = self.breakpoints.keys().copied().collect();
+        // watches and global conditions are checked after every step, so the folded instructions
+        // can only be used without them
+        let exact = !self.watches.is_empty() || self.break_condition.is_some();
+        let (breakpoints, watches, break_condition) =
+            (&self.breakpoints, &self.watches, &self.break_condition);
+        let pause = |i: &Interpreter| should_pause(i, breakpoints, watches, break_condition);
+        loop {
+            let result = if exact {
+                self.interpreter.run(FAST_RUN_CHUNK, pause)
+            } else {
+                self.interpreter.run_fast(FAST_RUN_CHUNK, &stops, pause)
+            };
+            match result {
+                Ok(RunOutcome::Running) if started.elapsed() < FAST_RUN_SLICE => {}
+                Ok(RunOutcome::Running) => break,
+                Ok(RunOutcome::Paused) => {
+                    self.state = State::Play;
+                    if self.selected == Pause {
+                        self.selected = Start;
+                    }
+                    break;
+                }
+                Ok(RunOutcome::Halted) => {
+                    self.state = State::Stop;
+                    if let Start | Pause | Run | Step | Speed = self.selected {
+                        self.selected = Reset;
+                    }
+                    break;
+                }
+                Ok(RunOutcome::NeedsInput) => {
+                    self.wait_for_input(State::FastRun);
+                    break;
+                }
+                Ok(RunOutcome::Dumped) => {
+                    self.record_dump();
+                    break;
+                }
+                Err(e) => {
+                    self.fail(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn step_interpreter(&mut self) {
+        match self.interpreter.step() {
+            Ok(StepOutcome::NeedsInput) => self.wait_for_input(self.state),
+            Ok(StepOutcome::Dumped) => self.record_dump(),
+            Ok(_) => {}
+            Err(e) => self.fail(e),
+        }
+    }
+
+    /// Logs the snapshot taken by `#`, pausing auto play and fast runs.
+    fn record_dump(&mut self) {
+        self.dumps.push(self.interpreter.dump());
+        if let State::AutoPlay | State::FastRun = self.state {
+            self.state = State::Play;
+            if self.selected == SelectItem::Pause {
+                self.selected = SelectItem::Start;
+            }
+        }
+    }
+
+    /// Pauses and lets the user enter a line, until which `,` cannot be executed.
+    fn wait_for_input(&mut self, resume: State) {
+        self.state = State::Play;
+        self.input_resume = resume;
+        self.edit_state = EditState::EnterInputLine;
+        self.selected = SelectItem::Input;
+        self.line_input = Input::default();
+        self.prompt_error = None;
+    }
+
+    fn resume_after_input(&mut self) {
+        self.edit_state = EditState::None;
+        self.prompt_error = None;
+        match self.input_resume {
+            State::AutoPlay | State::FastRun => {
+                self.state = self.input_resume;
+                self.selected = SelectItem::Pause;
+            }
+            _ => {
+                self.selected = SelectItem::Step;
+                self.step_interpreter();
+            }
+        }
+    }
+
+    fn fail(&mut self, e: RuntimeError) {
+        use SelectItem::*;
+        self.state = State::Failed;
+        self.runtime_error = Some(e);
+        if let Start | Pause | Run | Step | Speed = self.selected {
+            self.selected = Reset;
+        }
+    }
+
+    fn step_back_interpreter(&mut self) {
+        if self.interpreter.step_back() {
+            self.state = State::Play;
+            self.runtime_error = None;
+        }
+    }
+
+    fn run_back_to_breakpoint(&mut self) {
+        while self.interpreter.step_back() {
+            self.state = State::Play;
+            self.runtime_error = None;
+            if self.at_breakpoint() {
+                break;
+            }
+        }
+    }
+
+    fn should_pause(&self) -> bool {
+        should_pause(
+            &self.interpreter,
+            &self.breakpoints,
+            &self.watches,
+            &self.break_condition,
+        )
+    }
+
+    fn at_breakpoint(&self) -> bool {
+        at_breakpoint(&self.interpreter, &self.breakpoints)
+    }
+
+    /// Returns whether the first command has a breakpoint before the program is started, since
+    /// breakpoints are otherwise only checked after a step.
+    fn breakpoint_at_start(&self) -> bool {
+        self.state == State::Default
+            && self.interpreter.total_step_count() == 0
+            && self.at_breakpoint()
+    }
+
+    fn update_watch<F: FnOnce(&mut Watch)>(&mut self, f: F) {
+        let watch = self.watches.entry(self.memory_cursor).or_default();
+        f(watch);
+        if watch.is_empty() {
+            self.watches.remove(&self.memory_cursor);
+        }
+    }
+
+    fn open_prompt(&mut self, edit_state: EditState, value: String) {
+        self.prompt_input = Input::new(value);
+        self.prompt_error = None;
+        self.edit_state = edit_state;
+    }
+
+    /// Applies the edited prompt value, keeping the prompt open if it is invalid.
+    fn submit_prompt(&mut self) {
+        let value = self.prompt_input.value().trim().to_string();
+        let result = match self.edit_state {
+            EditState::EditWatchValue => parse_optional(&value, |v| {
+                v.parse::<i64>().map_err(|_| format!("invalid value: {v}"))
+            })
+            .map(|value| self.update_watch(|w| w.value = value)),
+            EditState::EditBreakpointCondition => {
+                parse_optional(&value, str::parse).map(|condition| {
+                    self.breakpoints.insert(self.source_cursor, condition);
+                })
+            }
+            EditState::EditBreakCondition => {
+                parse_optional(&value, str::parse).map(|condition| self.break_condition = condition)
+            }
+            EditState::None | EditState::EditInput | EditState::EnterInputLine => Ok(()),
+        };
+        match result {
+            Ok(()) => {
+                self.edit_state = EditState::None;
+                self.prompt_error = None;
+            }
+            Err(e) => {
+                self.prompt_error = Some(e);
+            }
+        }
+    }
+
+    fn move_memory_cursor(&mut self, delta: isize) {
+        let start = self.interpreter.memory_start();
+        let end = start + self.interpreter.memory().len() as isize - 1;
+        self.memory_cursor = (self.memory_cursor + delta).clamp(start, end);
+    }
+
+    /// Scrolls the Memory panel as little as possible so that the cell at `addr` is visible, and
+    /// returns the address of the first visible cell.
+    ///
+    /// In the grid layout, the rows start at multiples of the number of columns.
+    pub fn scroll_memory_to(&self, addr: Option<isize>) -> isize {
+        let start = self.interpreter.memory_start();
+        let end = start + self.interpreter.memory().len() as isize;
+        let width = self.memory_view_width.get().max(1) as isize;
+        let visible = width * self.memory_view_height.get().max(1) as isize;
+        let align = match self.memory_layout {
+            MemoryLayout::Row => 1,
+            MemoryLayout::Grid16 | MemoryLayout::Grid32 => width,
+        };
+        let offset = scroll_offset(self.memory_offset.get(), addr, start..end, visible, align);
+        self.memory_offset.set(offset);
+        offset
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        let cursor = self.source_cursor;
+        if !self.interpreter.command_positions().any(|p| p == cursor) {
+            return;
+        }
+        if self.breakpoints.remove(&cursor).is_none() {
+            self.breakpoints.insert(cursor, None);
+        }
+    }
+
+    /// Adds the `#` markers in the source and drops the breakpoints that no longer point to a command.
+    fn sync_breakpoints(&mut self) {
+        let source = self.source.join("\n");
+        for p in breakpoint_markers(&source, &self.dialect) {
+            self.breakpoints.entry(p).or_insert(None);
+        }
+        let commands: HashSet<_> = self.interpreter.command_positions().collect();
+        self.breakpoints.retain(|p, _| commands.contains(p));
+    }
+
+    fn move_source_cursor_line(&mut self, delta: isize) {
+        let (line, pos) = self.source_cursor;
+        let max_line = self.source.len().saturating_sub(1);
+        let line = line.saturating_add_signed(delta).min(max_line);
+        let line_len = self.source.get(line).map_or(0, |s| s.chars().count());
+        let pos = pos.min(line_len.saturating_sub(1));
+        self.move_source_cursor(Some((line, pos)));
+    }
+
+    fn move_source_cursor(&mut self, cursor: Option<(usize, usize)>) {
+        let Some(cursor) = cursor else {
+            return;
+        };
+        self.source_cursor = cursor;
+
+        let (line, _) = cursor;
+        let height = self.source_view_height.get().max(1);
+        if line < self.source_scroll_offset {
+            self.source_scroll_offset = line;
+        } else if line >= self.source_scroll_offset + height {
+            self.source_scroll_offset = line + 1 - height;
+        }
+    }
+
+    /// Re-reads the source file on Reset, so that fixes made in an editor are picked up, along
+    /// with the input after `!` unless the input was given otherwise.
+    fn reload_source(&mut self) {
+        // keep the current source if the file can no longer be read
+        if let Ok(content) = std::fs::read_to_string(&self.source_file) {
+            if self.input_from_source {
+                let (_, input) = self.dialect.split_input(&content);
+                self.input_bytes = input.map(|s| s.as_bytes().to_vec()).unwrap_or_default();
+                self.input_input = Input::new(escape::escape(&self.input_bytes));
+            }
+            let source: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+            if source != self.source {
+                self.coverage = Coverage::default();
+            }
+            self.source = source;
+            let max_line = self.source.len().saturating_sub(1);
+            self.source_scroll_offset = self.source_scroll_offset.min(max_line);
+            self.source_cursor.0 = self.source_cursor.0.min(max_line);
+        }
+    }
+
+    /// Returns the coverage of the previous runs merged with the current run.
+    pub fn coverage(&self) -> Coverage {
+        let mut coverage = Coverage::from_profile(&self.interpreter.profile());
+        // the previous runs are cleared when the source changes, so their commands match
+        let _ = coverage.merge(&self.coverage);
+        coverage
+    }
+
     fn reset_interpreter(&mut self) {
         let source = self.source.join("\n");
-        self.interpreter = Interpreter::new(&source, self.input_input.value())
+        self.interpreter = new_interpreter(
+            &source,
+            &self.input_bytes,
+            self.config,
+            &self.dialect,
+            self.interactive_input,
+        );
+        self.runtime_error = None;
+        self.dumps.clear();
+        self.memory_offset.set(self.interpreter.memory_start());
+        self.move_memory_cursor(0);
+    }
+}
+
+fn new_interpreter(
+    source: &str,
+    input: &[u8],
+    config: Config,
+    dialect: &Dialect,
+    interactive: bool,
+) -> Interpreter {
+    let interpreter = Interpreter::new(source, input, config, dialect);
+    if interactive {
+        interpreter.with_interactive_input()
+    } else {
+        interpreter
+    }
+}
+
+fn should_pause(
+    interpreter: &Interpreter,
+    breakpoints: &BTreeMap<(usize, usize), Option<Condition>>,
+    watches: &BTreeMap<isize, Watch>,
+    break_condition: &Option<Condition>,
+) -> bool {
+    at_breakpoint(interpreter, breakpoints)
+        || at_watch(interpreter, watches)
+        || break_condition
+            .as_ref()
+            .is_some_and(|c| c.eval(interpreter))
+}
+
+fn at_breakpoint(
+    interpreter: &Interpreter,
+    breakpoints: &BTreeMap<(usize, usize), Option<Condition>>,
+) -> bool {
+    let Some(lp) = interpreter.current_line_and_pos() else {
+        return false;
+    };
+    match breakpoints.get(&lp) {
+        Some(Some(condition)) => condition.eval(interpreter),
+        Some(None) => true,
+        None => false,
+    }
+}
+
+fn at_watch(interpreter: &Interpreter, watches: &BTreeMap<isize, Watch>) -> bool {
+    interpreter.last_access().is_some_and(|access| {
+        let addr = access.addr();
+        watches
+            .get(&addr)
+            .is_some_and(|w| w.triggered_by(access, interpreter.cell(addr)))
+    })
+}
+
+/// Parses a prompt value, where an empty value clears the setting.
+fn parse_optional<T, F>(value: &str, parse: F) -> Result<Option<T>, String>
+where
+    F: FnOnce(&str) -> Result<T, String>,
+{
+    if value.is_empty() {
+        Ok(None)
+    } else {
+        parse(value).map(Some)
+    }
+}
+
+fn initial_state(interpreter: &Interpreter) -> State {
+    if interpreter.diagnostics().is_empty() {
+        State::Default
+    } else {
+        State::Invalid
     }
 }
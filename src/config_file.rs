@@ -0,0 +1,156 @@
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, de::Error as _};
+
+use crate::interpreter::config::{CellSize, EofPolicy, OverflowPolicy, TapeSize};
+
+/// Interpreter settings read from the TOML config file:
+///
+/// ```toml
+/// cell_size = "u8"
+/// cell_overflow = "wrap"
+/// pointer_overflow = "error"
+/// tape_size = 30000 # or "infinite"
+/// eof = "unchanged"
+/// ```
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    #[serde(deserialize_with = "value_enum")]
+    pub cell_size: Option<CellSize>,
+    #[serde(deserialize_with = "value_enum")]
+    pub cell_overflow: Option<OverflowPolicy>,
+    #[serde(deserialize_with = "value_enum")]
+    pub pointer_overflow: Option<OverflowPolicy>,
+    #[serde(deserialize_with = "tape_size")]
+    pub tape_size: Option<TapeSize>,
+    #[serde(deserialize_with = "value_enum")]
+    pub eof: Option<EofPolicy>,
+}
+
+/// Loads the config file at the given path, or at the default location if no path is given.
+/// A missing file at the default location is not an error.
+pub fn load(path: Option<&str>) -> io::Result<FileConfig> {
+    let (path, required) = match path {
+        Some(path) => (PathBuf::from(path), true),
+        None => match default_path() {
+            Some(path) => (path, false),
+            None => return Ok(FileConfig::default()),
+        },
+    };
+    match fs::read_to_string(&path) {
+        Ok(content) => parse(&content).map_err(|e| io_error(&path, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound && !required => Ok(FileConfig::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(dir.join("termbf").join("config.toml"))
+}
+
+fn io_error(path: &Path, msg: String) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}", path.display(), msg),
+    )
+}
+
+fn parse(content: &str) -> Result<FileConfig, String> {
+    toml::from_str(content).map_err(|e| toml_error(content, &e))
+}
+
+/// Describes an error in a TOML file, prefixed with its line number.
+pub fn toml_error(content: &str, e: &toml::de::Error) -> String {
+    match e.span() {
+        Some(span) => {
+            let line = content[..span.start].matches('\n').count() + 1;
+            format!("{line}: {}", e.message())
+        }
+        None => e.message().to_string(),
+    }
+}
+
+/// Reads a string as one of the values accepted by the command line option.
+fn value_enum<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: ValueEnum,
+{
+    let value = String::deserialize(deserializer)?;
+    T::from_str(&value, false)
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+/// Reads the tape size as a number of cells or `"infinite"`.
+fn tape_size<'de, D>(deserializer: D) -> Result<Option<TapeSize>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value = match toml::Value::deserialize(deserializer)? {
+        toml::Value::String(name) => name,
+        value => value.to_string(),
+    };
+    value.parse().map(Some).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = r#"
+# termbf config
+cell_size = "i16" # signed
+cell_overflow = 'wrap'
+tape_size = "infinite"
+eof = "minus-one"
+"#;
+        let expected = FileConfig {
+            cell_size: Some(CellSize::I16),
+            cell_overflow: Some(OverflowPolicy::Wrap),
+            pointer_overflow: None,
+            tape_size: Some(TapeSize::Infinite),
+            eof: Some(EofPolicy::MinusOne),
+        };
+        assert_eq!(parse(content), Ok(expected));
+
+        let expected = FileConfig {
+            tape_size: Some(TapeSize::Fixed(1000)),
+            ..Default::default()
+        };
+        assert_eq!(parse("tape_size = 1000"), Ok(expected));
+        assert_eq!(parse(""), Ok(FileConfig::default()));
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(parse("eof = \"two\"").unwrap_err().starts_with("1: "));
+        assert!(
+            parse("\nfoo = 1")
+                .unwrap_err()
+                .starts_with("2: unknown field `foo`")
+        );
+        assert!(
+            parse("tape_size = 0")
+                .unwrap_err()
+                .starts_with("1: invalid tape size")
+        );
+        assert!(
+            parse("tape_size = -1")
+                .unwrap_err()
+                .starts_with("1: invalid tape size")
+        );
+        assert!(parse("cell_size = u8").is_err());
+        assert!(parse("cell_size").is_err());
+    }
+}
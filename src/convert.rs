@@ -0,0 +1,108 @@
+use crate::interpreter::dialect::{Command, Dialect};
+
+/// Converts the source to another dialect, keeping the line breaks and the indentation.
+///
+/// Comments are kept unless they contain lexemes of the target dialect, in which case only their
+/// line breaks are kept. Other whitespace between commands is replaced by the separator of the
+/// target dialect. The input following `!` with the separator extension is kept as it is.
+pub fn convert(source: &str, from: &Dialect, to: &Dialect) -> String {
+    let (source, input) = from.split_input(source);
+    let commands = from.commands(source);
+    let mut converted = join(source, &commands, to, true);
+    // a comment next to a lexeme can still form another lexeme, so drop all comments in that case
+    if !same_commands(&to.commands(&converted), &commands) {
+        converted = join(source, &commands, to, false);
+    }
+    converted.push_str(input.unwrap_or_default());
+    converted
+}
+
+fn same_commands(a: &[Command], b: &[Command]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.command == b.command)
+}
+
+fn join(source: &str, commands: &[Command], to: &Dialect, keep_comments: bool) -> String {
+    let lines: Vec<Vec<char>> = source.lines().map(|s| s.chars().collect()).collect();
+    let mut converted = String::with_capacity(source.len());
+    let mut pos = (0, 0);
+    for (i, c) in commands.iter().enumerate() {
+        let gap = text_between(&lines, pos, c.start);
+        let separator = if i == 0 { "" } else { to.separator() };
+        converted.push_str(&convert_gap(&gap, to, keep_comments, separator));
+        converted.push_str(&to.lexeme(c.command));
+        pos = (c.end.0, c.end.1 + 1);
+    }
+    let gap = text_between(&lines, pos, (lines.len(), 0));
+    converted.push_str(&convert_gap(&gap, to, keep_comments, ""));
+    if !source.ends_with('\n') && converted.ends_with('\n') {
+        converted.pop();
+    }
+    converted
+}
+
+/// Returns the text from `from` (inclusive) to `to` (exclusive), with the line breaks.
+fn text_between(lines: &[Vec<char>], from: (usize, usize), to: (usize, usize)) -> String {
+    let mut text = String::new();
+    for (line, chars) in lines.iter().enumerate().take(to.0 + 1).skip(from.0) {
+        let start = if line == from.0 { from.1 } else { 0 }.min(chars.len());
+        let end = if line == to.0 { to.1 } else { chars.len() };
+        text.extend(&chars[start..end]);
+        if line != to.0 {
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn convert_gap(gap: &str, to: &Dialect, keep_comments: bool, separator: &str) -> String {
+    let comment = gap.chars().any(|c| !c.is_whitespace());
+    if comment && keep_comments && to.commands(gap).is_empty() {
+        return gap.to_string();
+    }
+    match gap.rfind('\n') {
+        Some(i) => {
+            let breaks = "\n".repeat(gap.matches('\n').count());
+            let indent = &gap[i + 1..];
+            if indent.chars().all(char::is_whitespace) {
+                breaks + indent
+            } else {
+                breaks
+            }
+        }
+        None => separator.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::dialect::{DialectName, Extension};
+
+    #[test]
+    fn test_convert() {
+        let bf = Dialect::default();
+        let ook = Dialect::new(DialectName::Ook);
+
+        let source = "+[ loop\n    -]\n";
+        let converted = convert(source, &bf, &ook);
+        assert_eq!(
+            converted,
+            "Ook. Ook. Ook! Ook? loop\n    Ook! Ook! Ook? Ook!\n"
+        );
+        assert_eq!(convert(&converted, &ook, &bf), source);
+
+        // comments containing lexemes of the target dialect are dropped
+        assert_eq!(convert("Ook. Ook. a+b\nOok! Ook!", &ook, &bf), "+\n-");
+        assert_eq!(convert("+ Ook! Ook?\n.", &bf, &ook), "Ook. Ook.\nOok! Ook.");
+        // comments that would form a lexeme together with a command are also dropped
+        assert_eq!(convert("Ook! +", &bf, &ook), "Ook. Ook.");
+
+        let extensions = [Extension::Dump, Extension::Separator];
+        let bf = bf.with_extensions(&extensions).unwrap();
+        let ook = ook.with_extensions(&extensions).unwrap();
+        assert_eq!(
+            convert("+ #\n,!+ \n", &bf, &ook),
+            "Ook. Ook. #\nOok. Ook! !+ \n"
+        );
+    }
+}
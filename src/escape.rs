@@ -0,0 +1,135 @@
+/// Converts the text entered in the Input panel to the bytes given to the program.
+///
+/// The text is UTF-8 encoded, except for the escape sequences `\n`, `\t`, `\r`, `\0`, `\\` and
+/// `\xNN`, where `NN` is a byte in two hex digits.
+pub fn unescape(s: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut chars = s.chars().enumerate();
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        let column = i + 1;
+        match chars.next().map(|(_, c)| c) {
+            Some('n') => bytes.push(b'\n'),
+            Some('t') => bytes.push(b'\t'),
+            Some('r') => bytes.push(b'\r'),
+            Some('0') => bytes.push(0),
+            Some('\\') => bytes.push(b'\\'),
+            Some('x') => {
+                let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("column {column}: `\\x` needs two hex digits"));
+                }
+                bytes.push(u8::from_str_radix(&hex, 16).unwrap());
+            }
+            Some(c) => return Err(format!("column {column}: unknown escape `\\{c}`")),
+            None => return Err(format!("column {column}: incomplete escape `\\`")),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Converts the bytes back to text that `unescape` decodes to the same bytes.
+///
+/// ASCII control characters, backslashes and bytes that are not valid UTF-8 are escaped.
+pub fn escape(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            match c {
+                '\n' => s.push_str("\\n"),
+                '\t' => s.push_str("\\t"),
+                '\r' => s.push_str("\\r"),
+                '\0' => s.push_str("\\0"),
+                '\\' => s.push_str("\\\\"),
+                c if c.is_ascii_control() => s.push_str(&format!("\\x{:02x}", c as u8)),
+                c => s.push(c),
+            }
+        }
+        for b in chunk.invalid() {
+            s.push_str(&format!("\\x{b:02x}"));
+        }
+    }
+    s
+}
+
+/// Renders the bytes for display, decoded as UTF-8 where possible.
+///
+/// Control characters are shown as the symbols of the Control Pictures block (e.g. `␊` for a
+/// newline), and bytes that are not valid UTF-8 as `\xNN`.
+pub fn preview(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            s.push(control_picture(c).unwrap_or(c));
+        }
+        for b in chunk.invalid() {
+            s.push_str(&format!("\\x{b:02x}"));
+        }
+    }
+    s
+}
+
+fn control_picture(c: char) -> Option<char> {
+    match c {
+        '\0'..='\x1f' => char::from_u32(0x2400 + c as u32),
+        '\x7f' => Some('\u{2421}'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("abc").unwrap(), b"abc");
+        assert_eq!(unescape("a\\nb\\t\\r\\0\\\\").unwrap(), b"a\nb\t\r\0\\");
+        assert_eq!(unescape("\\x41\\xfF\\x00").unwrap(), b"A\xff\0");
+        assert_eq!(unescape("あ\\n").unwrap(), "あ\n".as_bytes());
+
+        assert_eq!(
+            unescape("ab\\q").unwrap_err(),
+            "column 3: unknown escape `\\q`"
+        );
+        assert_eq!(
+            unescape("\\x+1").unwrap_err(),
+            "column 1: `\\x` needs two hex digits"
+        );
+        assert_eq!(
+            unescape("\\x4").unwrap_err(),
+            "column 1: `\\x` needs two hex digits"
+        );
+        assert_eq!(
+            unescape("あ\\xg0").unwrap_err(),
+            "column 2: `\\x` needs two hex digits"
+        );
+        assert_eq!(
+            unescape("a\\").unwrap_err(),
+            "column 2: incomplete escape `\\`"
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape(b"a\nb\t\r\0\\\x1b\x7f"),
+            "a\\nb\\t\\r\\0\\\\\\x1b\\x7f"
+        );
+        assert_eq!(escape(b"\xff\xe3\x81\x82"), "\\xffあ");
+
+        let bytes: Vec<u8> = (0..=255).collect();
+        assert_eq!(unescape(&escape(&bytes)).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview(b"Hi\n\0\t\x7f"), "Hi␊␀␉␡");
+        assert_eq!(preview("あ".as_bytes()), "あ");
+        assert_eq!(preview(b"a\xffb\xe3\x81"), "a\\xffb\\xe3\\x81");
+    }
+}
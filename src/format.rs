@@ -0,0 +1,83 @@
+use crate::interpreter::dialect::Dialect;
+
+const INDENT: &str = "    ";
+
+/// Formats the source by indenting each line starting with a command by the loop depth at its
+/// start, and removing trailing whitespace.
+///
+/// Only whitespace is changed, so the comments and the commands are kept as they are. Lines
+/// starting with `]` are indented to the depth of the matching `[`, and lines starting with a
+/// comment keep their indentation. The input following `!` with the separator extension is kept
+/// as it is.
+pub fn format(source: &str, dialect: &Dialect) -> String {
+    let (program, input) = dialect.split_input(source);
+    let mut formatted = format_program(program, dialect);
+    if let Some(input) = input {
+        formatted.pop(); // the line break is part of the input if any
+        formatted.push_str(input);
+    }
+    formatted
+}
+
+fn format_program(source: &str, dialect: &Dialect) -> String {
+    let mut line_commands = vec![Vec::new(); source.lines().count()];
+    for c in dialect.commands(source) {
+        line_commands[c.start.0].push(c);
+    }
+
+    let mut formatted = String::with_capacity(source.len());
+    let mut depth: usize = 0;
+    for (line, commands) in source.lines().zip(&line_commands) {
+        let line = line.trim_end();
+        let trimmed = line.trim_start();
+        let indent_len = line.chars().count() - trimmed.chars().count();
+        match commands.first() {
+            Some(c) if c.start.1 == indent_len => {
+                let indent = if c.command == ']' {
+                    depth.saturating_sub(1)
+                } else {
+                    depth
+                };
+                formatted.push_str(&INDENT.repeat(indent));
+                formatted.push_str(trimmed);
+            }
+            _ => formatted.push_str(line),
+        }
+        formatted.push('\n');
+
+        for c in commands {
+            match c.command {
+                '[' => depth += 1,
+                ']' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::dialect::{DialectName, Extension};
+
+    #[test]
+    fn test_format() {
+        let source = "  +++ set\n[ loop  \n>+[\n  comment\n-]\n  ]   \n\n\t.\n";
+        let expected = "+++ set\n[ loop\n    >+[\n  comment\n        -]\n]\n\n.\n";
+        let brainfuck = Dialect::default();
+        assert_eq!(format(source, &brainfuck), expected);
+        assert_eq!(format(expected, &brainfuck), expected);
+        assert_eq!(format("+", &brainfuck), "+\n");
+
+        let ook = Dialect::new(DialectName::Ook);
+        let source = "Ook! Ook? Ook. Ook.\n Ook? Ook!";
+        let expected = "Ook! Ook? Ook. Ook.\nOok? Ook!\n";
+        assert_eq!(format(source, &ook), expected);
+
+        let brainfuck = brainfuck.with_extensions(&[Extension::Separator]).unwrap();
+        let source = " [\n+] ! input  \n  [ \n";
+        let expected = "[\n    +] ! input  \n  [ \n";
+        assert_eq!(format(source, &brainfuck), expected);
+    }
+}
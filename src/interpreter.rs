@@ -1,62 +1,174 @@
-use std::collections::HashMap;
+pub mod condition;
+pub mod config;
+pub mod coverage;
+pub mod dialect;
+mod history;
+mod ir;
+pub mod profile;
+
+use std::rc::Rc;
+
+use config::{Config, EofPolicy, OverflowPolicy, TapeSize};
+use dialect::{Dialect, Extension};
+use history::{Checkpoint, History, Undo};
+use ir::{Instr, Op, Program};
+use profile::{CommandCount, LoopCount, Profile};
+
+const INFINITE_TAPE_CHUNK: usize = 256;
 
 pub struct Interpreter {
-    memory: Vec<u8>,
-    ptr: u8,
+    config: Config,
+    memory: Vec<i64>,
+    origin: usize, // index of address 0 in memory
+    ptr: isize,
 
     tokens: Vec<Token>,
-    jump_map: HashMap<Token, usize>,
+    jump_targets: Vec<Option<usize>>,
+    program: Rc<Program>,
+    diagnostics: Vec<Diagnostic>,
     cur: usize,
-    input: String,
-    output: String,
+    counts: Vec<usize>, // number of executions of each token
+    input: Vec<u8>,
+    input_pos: usize, // number of input bytes consumed
+    input_closed: bool,
+    interactive: bool,
+    output: Vec<u8>,
 
     step_count: usize,
+    history: History,
+    last_access: Option<CellAccess>,
 }
 
 impl Interpreter {
-    pub fn new(source: &str, input: &str) -> Interpreter {
-        let tokens = tokenize(source);
-        let jump_map = build_jump_map(&tokens);
+    pub fn new(source: &str, input: &[u8], config: Config, dialect: &Dialect) -> Interpreter {
+        let tokens = tokenize(source, dialect);
+        let jump_targets = build_jump_targets(&tokens);
+        let program = Rc::new(Program::compile(&tokens, &jump_targets));
+        let diagnostics = validate(&tokens);
+        let counts = vec![0; tokens.len()];
         Interpreter {
-            memory: vec![0; u8::MAX as usize],
+            config,
+            memory: vec![0; initial_tape_len(config.tape_size)],
+            origin: 0,
             ptr: 0,
             tokens,
-            jump_map,
+            jump_targets,
+            program,
+            diagnostics,
             cur: 0,
-            input: input.to_string(),
-            output: String::new(),
+            counts,
+            input: input.to_vec(),
+            input_pos: 0,
+            input_closed: false,
+            interactive: false,
+            output: Vec::new(),
             step_count: 0,
+            history: History::default(),
+            last_access: None,
         }
     }
 
-    pub fn input(&self) -> &str {
-        &self.input
+    /// Stops recording the history, so that `step_back` is no longer possible.
+    pub fn without_history(mut self) -> Interpreter {
+        self.history = History::disabled();
+        self
+    }
+
+    /// Makes `,` wait for more input with `StepOutcome::NeedsInput` when the input is exhausted,
+    /// instead of reading EOF, until `close_input` is called.
+    pub fn with_interactive_input(mut self) -> Interpreter {
+        self.interactive = true;
+        self
     }
 
-    pub fn set_input(&mut self, input: String) {
+    /// Returns the input bytes not yet read by `,`.
+    pub fn input(&self) -> &[u8] {
+        &self.input[self.input_pos..]
+    }
+
+    pub fn set_input(&mut self, input: Vec<u8>) {
         self.input = input;
+        self.input_pos = 0;
+        self.input_closed = false;
+    }
+
+    /// Appends bytes to the input, e.g. after `StepOutcome::NeedsInput`.
+    pub fn push_input(&mut self, bytes: &[u8]) {
+        self.input.extend_from_slice(bytes);
+        self.input_closed = false;
+    }
+
+    /// Makes `,` read EOF once the remaining input is exhausted, also in interactive mode.
+    pub fn close_input(&mut self) {
+        self.input_closed = true;
+    }
+
+    /// Whether the next step is `,` waiting for more input in interactive mode.
+    pub fn needs_input(&self) -> bool {
+        self.interactive
+            && !self.input_closed
+            && self.input_pos >= self.input.len()
+            && self
+                .current_token()
+                .is_some_and(|t| t.tp == TokenType::Comma)
     }
 
-    pub fn output(&self) -> &str {
+    pub fn output(&self) -> &[u8] {
         &self.output
     }
 
-    pub fn memory(&self) -> &Vec<u8> {
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn memory(&self) -> &[i64] {
         &self.memory
     }
 
-    pub fn current_ptr(&self) -> u8 {
+    /// Address of the first cell of `memory()`, which is negative if an infinite tape has grown to the left.
+    pub fn memory_start(&self) -> isize {
+        -(self.origin as isize)
+    }
+
+    pub fn current_ptr(&self) -> isize {
         self.ptr
     }
 
+    /// Value of the cell at `addr`, which is 0 for cells an infinite tape has not allocated yet.
+    pub fn cell(&self, addr: isize) -> i64 {
+        self.cell_index(addr).map_or(0, |idx| self.memory[idx])
+    }
+
+    /// Cell read or written by the last executed step.
+    pub fn last_access(&self) -> Option<CellAccess> {
+        self.last_access
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
     pub fn total_step_count(&self) -> usize {
         self.step_count
     }
 
+    pub fn command_positions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.tokens.iter().map(|t| (t.line, t.pos))
+    }
+
     pub fn current_line_and_pos(&self) -> Option<(usize, usize)> {
         self.current_token().map(|t| (t.line, t.pos))
     }
 
+    /// Returns the position of the last character of the command starting at the position.
+    pub fn command_end(&self, line_and_pos: (usize, usize)) -> Option<(usize, usize)> {
+        let i = self
+            .tokens
+            .binary_search_by_key(&line_and_pos, |t| (t.line, t.pos))
+            .ok()?;
+        Some(self.tokens[i].end)
+    }
+
     pub fn end(&self) -> bool {
         self.cur >= self.tokens.len()
     }
@@ -65,87 +177,748 @@ impl Interpreter {
         self.cur > 0
     }
 
-    pub fn step(&mut self) {
-        if let Some(token) = self.current_token() {
-            match token.tp {
-                TokenType::Plus => {
-                    let v = self.current_value();
-                    *v = v.checked_add(1).unwrap();
+    pub fn step(&mut self) -> Result<StepOutcome, RuntimeError> {
+        let Some(token) = self.current_token() else {
+            return Ok(StepOutcome::Halted);
+        };
+        if self.needs_input() {
+            return Ok(StepOutcome::NeedsInput);
+        }
+        let (line, pos, step) = (token.line, token.pos, self.step_count + 1);
+        let cell_size = self.config.cell_size;
+
+        if self.history.needs_checkpoint(self.step_count) {
+            self.history.push_checkpoint(self.checkpoint());
+        }
+        let mut undo = Undo {
+            cur: self.cur,
+            ptr: self.ptr,
+            cell: self
+                .cell_index(self.ptr)
+                .map(|idx| (self.ptr, self.memory[idx])),
+            input: false,
+            output_len: self.output.len(),
+        };
+        let (cell_min, cell_max) = (cell_size.min(), cell_size.max());
+        let cell_overflow = self.config.cell_overflow;
+        self.last_access = None;
+        let ptr = self.ptr;
+
+        match token.tp {
+            TokenType::Plus => {
+                let v = self.current_value(&token)?;
+                *v = cell_overflow
+                    .apply(*v + 1, cell_min, cell_max)
+                    .ok_or(RuntimeError::CellOverflow { line, pos, step })?;
+                self.last_access = Some(CellAccess::Write(ptr));
+                self.cur += 1;
+            }
+            TokenType::Minus => {
+                let v = self.current_value(&token)?;
+                *v = cell_overflow
+                    .apply(*v - 1, cell_min, cell_max)
+                    .ok_or(RuntimeError::CellUnderflow { line, pos, step })?;
+                self.last_access = Some(CellAccess::Write(ptr));
+                self.cur += 1;
+            }
+            TokenType::RightAngle => {
+                self.move_ptr(1)
+                    .ok_or(RuntimeError::PointerOutOfRange { line, pos, step })?;
+                self.cur += 1;
+            }
+            TokenType::LeftAngle => {
+                self.move_ptr(-1)
+                    .ok_or(RuntimeError::PointerOutOfRange { line, pos, step })?;
+                self.cur += 1;
+            }
+            TokenType::LeftSquare => {
+                let v = self.current_value(&token)?;
+                if *v == 0 {
+                    self.cur = self.jump_idx(&token)?;
+                } else {
                     self.cur += 1;
                 }
-                TokenType::Minus => {
-                    let v = self.current_value();
-                    *v = v.checked_sub(1).unwrap();
+                self.last_access = Some(CellAccess::Read(ptr));
+            }
+            TokenType::RightSquare => {
+                let v = self.current_value(&token)?;
+                if *v != 0 {
+                    self.cur = self.jump_idx(&token)?;
+                } else {
                     self.cur += 1;
                 }
-                TokenType::RightAngle => {
-                    self.ptr = self.ptr.checked_add(1).unwrap();
-                    self.cur += 1;
+                self.last_access = Some(CellAccess::Read(ptr));
+            }
+            TokenType::Dot => {
+                let v = self.current_value(&token)?;
+                let b = cell_size.output_byte(*v);
+                self.output.push(b);
+                self.last_access = Some(CellAccess::Read(ptr));
+                self.cur += 1;
+            }
+            TokenType::Comma => {
+                let b = self.input.get(self.input_pos).copied();
+                let eof = self.config.eof;
+                let v = self.current_value(&token)?;
+                match (b, eof) {
+                    (Some(b), _) => *v = cell_size.input_value(b),
+                    (None, EofPolicy::Zero) => *v = 0,
+                    (None, EofPolicy::MinusOne) => *v = cell_size.minus_one(),
+                    (None, EofPolicy::Unchanged) => {}
                 }
-                TokenType::LeftAngle => {
-                    self.ptr = self.ptr.checked_sub(1).unwrap();
-                    self.cur += 1;
+                if b.is_some() {
+                    self.input_pos += 1;
+                    undo.input = true;
                 }
-                TokenType::LeftSquare => {
-                    let v = self.current_value();
-                    if *v == 0 {
-                        self.cur = self.jump_idx(&token);
-                    } else {
-                        self.cur += 1;
-                    }
+                self.last_access = Some(CellAccess::Write(ptr));
+                self.cur += 1;
+            }
+            TokenType::Hash => {
+                self.cur += 1;
+            }
+        }
+
+        self.step_count += 1;
+        self.counts[undo.cur] += 1;
+        self.history.push_undo(undo);
+        match token.tp {
+            TokenType::Hash => Ok(StepOutcome::Dumped),
+            _ => Ok(StepOutcome::Executed),
+        }
+    }
+
+    /// Returns the execution counts of the commands and the loops.
+    pub fn profile(&self) -> Profile {
+        let commands = self
+            .tokens
+            .iter()
+            .zip(&self.counts)
+            .map(|(t, count)| CommandCount {
+                command: t.tp.command(),
+                start: (t.line, t.pos),
+                end: t.end,
+                count: *count,
+            })
+            .collect();
+        let loops = self
+            .tokens
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.tp == TokenType::LeftSquare)
+            .filter_map(|(i, t)| {
+                let end = self.jump_targets[i]? - 1;
+                Some(LoopCount {
+                    start: (t.line, t.pos),
+                    end: (self.tokens[end].line, self.tokens[end].pos),
+                    iterations: self.counts[end],
+                    steps: self.counts[i..=end].iter().sum(),
+                })
+            })
+            .collect();
+        Profile {
+            commands,
+            loops,
+            total_steps: self.step_count,
+        }
+    }
+
+    /// Returns a snapshot of the memory for the `#` command that was just executed.
+    ///
+    /// The cells range from address 0, or the pointer if it is negative, to the last non-zero
+    /// cell or the pointer.
+    pub fn dump(&self) -> Dump {
+        let start = self.ptr.min(0);
+        let last_nonzero = self.memory.iter().rposition(|v| *v != 0);
+        let end = last_nonzero.map_or(0, |idx| idx as isize - self.origin as isize);
+        let end = end.max(self.ptr);
+        let (line, pos) = self.cur.checked_sub(1).map_or((0, 0), |i| {
+            let t = &self.tokens[i];
+            (t.line, t.pos)
+        });
+        Dump {
+            line,
+            pos,
+            step: self.step_count,
+            ptr: self.ptr,
+            start,
+            cells: (start..=end).map(|addr| self.cell(addr)).collect(),
+        }
+    }
+
+    /// Undoes the last executed step. Returns `false` if there is no step that can be undone.
+    pub fn step_back(&mut self) -> bool {
+        if self.step_count == 0 {
+            return false;
+        }
+        self.last_access = None;
+
+        if let Some(undo) = self.history.pop_undo() {
+            self.cur = undo.cur;
+            self.ptr = undo.ptr;
+            if let Some((addr, v)) = undo.cell {
+                if let Some(idx) = self.cell_index(addr) {
+                    self.memory[idx] = v;
                 }
-                TokenType::RightSquare => {
-                    let v = self.current_value();
-                    if *v != 0 {
-                        self.cur = self.jump_idx(&token);
-                    } else {
-                        self.cur += 1;
-                    }
+            }
+            if undo.input {
+                self.input_pos -= 1;
+            }
+            self.output.truncate(undo.output_len);
+            self.counts[undo.cur] -= 1;
+            self.step_count -= 1;
+            return true;
+        }
+
+        // older steps are no longer recorded individually, so replay from a checkpoint
+        let target = self.step_count - 1;
+        let Some(checkpoint) = self.history.checkpoint_before(self.step_count) else {
+            return false;
+        };
+        self.restore(checkpoint);
+        while self.step_count < target {
+            if self.step().is_err() {
+                return false;
+            }
+        }
+        self.last_access = None;
+        true
+    }
+
+    /// Executes up to `max_steps` steps, stopping early when `pause` returns true after a step.
+    pub fn run<F>(&mut self, max_steps: usize, mut pause: F) -> Result<RunOutcome, RuntimeError>
+    where
+        F: FnMut(&Interpreter) -> bool,
+    {
+        for _ in 0..max_steps {
+            match self.step()? {
+                StepOutcome::Executed => {}
+                StepOutcome::Halted => return Ok(RunOutcome::Halted),
+                StepOutcome::NeedsInput => return Ok(RunOutcome::NeedsInput),
+                StepOutcome::Dumped => return Ok(RunOutcome::Dumped),
+            }
+            if pause(self) {
+                return Ok(RunOutcome::Paused);
+            }
+        }
+        Ok(self.run_outcome())
+    }
+
+    /// Executes up to `max_instrs` instructions of the optimized program, stopping early when
+    /// `pause` returns true between instructions.
+    ///
+    /// Folded instructions are executed at once, so `pause` is not called in the middle of them,
+    /// except for the ones containing a command at one of the `stops` positions, which are executed
+    /// token by token. The result is the same as calling `step` repeatedly, including the step
+    /// count and the errors, but the undo entries of the executed steps are not recorded.
+    pub fn run_fast<F>(
+        &mut self,
+        max_instrs: usize,
+        stops: &[(usize, usize)],
+        mut pause: F,
+    ) -> Result<RunOutcome, RuntimeError>
+    where
+        F: FnMut(&Interpreter) -> bool,
+    {
+        let mut stop_tokens = vec![false; self.tokens.len()];
+        for lp in stops {
+            if let Ok(i) = self.tokens.binary_search_by_key(lp, |t| (t.line, t.pos)) {
+                stop_tokens[i] = true;
+            }
+        }
+
+        let result = self.run_instrs(max_instrs, &stop_tokens, &mut pause);
+        // the recorded undo entries no longer match the state after folded instructions,
+        // so stepping back restores a checkpoint instead
+        self.history.clear_undo();
+        result
+    }
+
+    fn run_instrs<F>(
+        &mut self,
+        max_instrs: usize,
+        stop_tokens: &[bool],
+        pause: &mut F,
+    ) -> Result<RunOutcome, RuntimeError>
+    where
+        F: FnMut(&Interpreter) -> bool,
+    {
+        let program = Rc::clone(&self.program);
+        for _ in 0..max_instrs {
+            if self.end() {
+                return Ok(RunOutcome::Halted);
+            }
+            let executed = match program.instr_at(self.cur) {
+                Some(i) => {
+                    let instr = &program.instrs()[i];
+                    let interior = instr.start + 1..instr.start + instr.len;
+                    !stop_tokens[interior].contains(&true) && self.exec_instr(instr)
                 }
-                TokenType::Dot => {
-                    let v = self.current_value();
-                    let c = *v as char;
-                    self.output.push(c);
-                    self.cur += 1;
+                None => false,
+            };
+            if !executed {
+                match self.step()? {
+                    StepOutcome::NeedsInput => return Ok(RunOutcome::NeedsInput),
+                    StepOutcome::Dumped => return Ok(RunOutcome::Dumped),
+                    StepOutcome::Executed | StepOutcome::Halted => {}
                 }
-                TokenType::Comma => {
-                    let mut cs = self.input.chars();
-                    let c = cs.next().unwrap_or(0 as char); // EOF: 0
-                    self.input = cs.collect();
-                    let v = self.current_value();
-                    *v = c as u8;
-                    self.cur += 1;
+            }
+            if pause(self) {
+                return Ok(RunOutcome::Paused);
+            }
+        }
+        Ok(self.run_outcome())
+    }
+
+    fn run_outcome(&self) -> RunOutcome {
+        if self.end() {
+            RunOutcome::Halted
+        } else {
+            RunOutcome::Running
+        }
+    }
+
+    /// Executes an instruction at once, returning false without changing the state if it has to be
+    /// executed token by token to get the same result, e.g. because it overflows.
+    fn exec_instr(&mut self, instr: &Instr) -> bool {
+        if self.history.needs_checkpoint(self.step_count) {
+            self.history.push_checkpoint(self.checkpoint());
+        }
+        let (start, len) = (instr.start, instr.len);
+        let cell_size = self.config.cell_size;
+        let (cell_min, cell_max) = (cell_size.min(), cell_size.max());
+        let cell_overflow = self.config.cell_overflow;
+        let Some(idx) = self.cell_index(self.ptr) else {
+            return false;
+        };
+        let v = self.memory[idx];
+
+        // number of iterations until a loop decrementing or incrementing the cell by one reaches 0
+        let iterations = |delta: i64| match (v.signum() == -delta, cell_overflow) {
+            (true, _) => Some(v.unsigned_abs()),
+            (false, OverflowPolicy::Wrap) => Some(cell_size.bit_pattern(-v * delta)),
+            (false, _) => None,
+        };
+
+        let (next, steps, access) = match &instr.op {
+            Op::Add(n) => {
+                let Some(nv) = cell_overflow.apply(v + n, cell_min, cell_max) else {
+                    return false;
+                };
+                self.memory[idx] = nv;
+                self.count_loop(start, len, 1);
+                (start + len, len, Some(CellAccess::Write(self.ptr)))
+            }
+            Op::Move(n) => {
+                if self.move_ptr(*n).is_none() {
+                    return false;
                 }
+                self.count_loop(start, len, 1);
+                (start + len, len, None)
+            }
+            Op::JumpIfZero(end) => {
+                let next = if v == 0 { *end } else { start + 1 };
+                self.counts[start] += 1;
+                (next, 1, Some(CellAccess::Read(self.ptr)))
             }
+            Op::JumpIfNonZero(body) => {
+                let next = if v != 0 { *body } else { start + 1 };
+                self.counts[start] += 1;
+                (next, 1, Some(CellAccess::Read(self.ptr)))
+            }
+            Op::Clear(_) | Op::MulLoop { .. } if v == 0 => {
+                self.counts[start] += 1;
+                (start + len, 1, Some(CellAccess::Read(self.ptr)))
+            }
+            Op::Clear(delta) => {
+                let Some(k) = iterations(*delta) else {
+                    return false;
+                };
+                self.memory[idx] = 0;
+                self.counts[start] += 1;
+                self.count_loop(start + 1, len - 1, k as usize);
+                (
+                    start + len,
+                    1 + 2 * k as usize,
+                    Some(CellAccess::Write(self.ptr)),
+                )
+            }
+            Op::MulLoop {
+                targets,
+                monotonic,
+                range,
+            } => {
+                let Some(k) = iterations(-1) else {
+                    return false;
+                };
+                if !*monotonic && cell_overflow != OverflowPolicy::Wrap {
+                    return false;
+                }
+                let Some(values) = self.mul_loop_values(targets, *range, k as i64) else {
+                    return false;
+                };
+                for (addr, nv) in values {
+                    let idx = self.cell_index(addr).unwrap();
+                    self.memory[idx] = nv;
+                }
+                let idx = self.cell_index(self.ptr).unwrap();
+                self.memory[idx] = 0;
+                self.counts[start] += 1;
+                self.count_loop(start + 1, len - 1, k as usize);
+                (
+                    start + len,
+                    1 + k as usize * (len - 1),
+                    Some(CellAccess::Write(self.ptr)),
+                )
+            }
+            Op::Step => return false,
+        };
+
+        self.cur = next;
+        self.step_count += steps;
+        self.last_access = access;
+        true
+    }
+
+    /// Adds `n` executions to each of `len` tokens from `start`.
+    fn count_loop(&mut self, start: usize, len: usize, n: usize) {
+        for count in &mut self.counts[start..start + len] {
+            *count += n;
         }
+    }
 
-        self.step_count += 1;
+    /// Returns the new values of the cells changed by `k` iterations of a multiply loop, allocating
+    /// the cells visited by the pointer on an infinite tape.
+    ///
+    /// Returns `None` if the pointer would leave a fixed tape within the offsets in `range`, so
+    /// that the loop is stepped and reports the error or applies the pointer overflow policy.
+    fn mul_loop_values(
+        &mut self,
+        targets: &[(isize, i64)],
+        (min, max): (isize, isize),
+        k: i64,
+    ) -> Option<Vec<(isize, i64)>> {
+        let cell_size = self.config.cell_size;
+        let cell_overflow = self.config.cell_overflow;
+        if let TapeSize::Fixed(n) = self.config.tape_size {
+            if self.ptr + min < 0 || self.ptr + max >= n as isize {
+                return None;
+            }
+        }
+        let values: Option<Vec<_>> = targets
+            .iter()
+            .map(|(o, d)| {
+                let addr = self.ptr + o;
+                let v = self.cell(addr).checked_add(d.checked_mul(k)?)?;
+                let v = cell_overflow.apply(v, cell_size.min(), cell_size.max())?;
+                Some((addr, v))
+            })
+            .collect();
+        if values.is_some() && self.config.tape_size == TapeSize::Infinite {
+            self.grow_tape_to(self.ptr + min);
+            self.grow_tape_to(self.ptr + max);
+        }
+        values
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            step_count: self.step_count,
+            memory: self.memory.clone(),
+            origin: self.origin,
+            ptr: self.ptr,
+            cur: self.cur,
+            input_pos: self.input_pos,
+            output: self.output.clone(),
+            counts: self.counts.clone(),
+        }
+    }
+
+    fn restore(&mut self, checkpoint: Checkpoint) {
+        self.step_count = checkpoint.step_count;
+        self.memory = checkpoint.memory;
+        self.origin = checkpoint.origin;
+        self.ptr = checkpoint.ptr;
+        self.cur = checkpoint.cur;
+        self.input_pos = checkpoint.input_pos;
+        self.output = checkpoint.output;
+        self.counts = checkpoint.counts;
     }
 
     fn current_token(&self) -> Option<Token> {
         self.tokens.get(self.cur).copied()
     }
 
-    fn current_value(&mut self) -> &mut u8 {
-        self.memory.get_mut(self.ptr as usize).unwrap()
+    fn current_value(&mut self, token: &Token) -> Result<&mut i64, RuntimeError> {
+        let err = RuntimeError::PointerOutOfRange {
+            line: token.line,
+            pos: token.pos,
+            step: self.step_count + 1,
+        };
+        match self.cell_index(self.ptr) {
+            Some(idx) => Ok(&mut self.memory[idx]),
+            None => Err(err),
+        }
+    }
+
+    fn cell_index(&self, addr: isize) -> Option<usize> {
+        usize::try_from(addr + self.origin as isize)
+            .ok()
+            .filter(|idx| *idx < self.memory.len())
+    }
+
+    fn move_ptr(&mut self, delta: isize) -> Option<()> {
+        let next = self.ptr + delta;
+        match self.config.tape_size {
+            TapeSize::Fixed(n) => {
+                let policy = self.config.pointer_overflow;
+                self.ptr = policy.apply(next as i64, 0, n as i64 - 1)? as isize;
+            }
+            TapeSize::Infinite => {
+                self.ptr = next;
+                self.grow_tape();
+            }
+        }
+        Some(())
+    }
+
+    fn grow_tape(&mut self) {
+        self.grow_tape_to(self.ptr);
     }
 
-    fn jump_idx(&self, token: &Token) -> usize {
-        *self.jump_map.get(token).unwrap()
+    fn grow_tape_to(&mut self, addr: isize) {
+        let idx = addr + self.origin as isize;
+        if idx < 0 {
+            let n = (-idx as usize).next_multiple_of(INFINITE_TAPE_CHUNK);
+            self.memory.splice(0..0, std::iter::repeat_n(0, n));
+            self.origin += n;
+        } else if idx as usize >= self.memory.len() {
+            let len = (idx as usize + 1).next_multiple_of(INFINITE_TAPE_CHUNK);
+            self.memory.resize(len, 0);
+        }
+    }
+
+    fn jump_idx(&self, token: &Token) -> Result<usize, RuntimeError> {
+        self.jump_targets[self.cur].ok_or(RuntimeError::UnmatchedBracket {
+            line: token.line,
+            pos: token.pos,
+            step: self.step_count + 1,
+        })
     }
 }
 
-fn tokenize(source: &str) -> Vec<Token> {
-    let mut tokens = Vec::new();
-    for (line, s) in source.lines().enumerate() {
-        for (pos, c) in s.chars().enumerate() {
-            if let Some(tp) = char_to_token_type(c) {
-                let token = Token::new(tp, line, pos);
-                tokens.push(token);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepOutcome {
+    Executed,
+    Halted,
+    /// `,` was not executed because the input is exhausted in interactive mode.
+    NeedsInput,
+    /// `#` was executed, and `Interpreter::dump` returns its snapshot.
+    Dumped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The step limit was reached before the program ended.
+    Running,
+    Paused,
+    Halted,
+    /// Stopped before `,` because the input is exhausted in interactive mode.
+    NeedsInput,
+    /// Stopped after `#`.
+    Dumped,
+}
+
+/// Snapshot of the memory taken by `#`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dump {
+    pub line: usize,
+    pub pos: usize,
+    pub step: usize,
+    pub ptr: isize,
+    /// Address of the first cell.
+    pub start: isize,
+    pub cells: Vec<i64>,
+}
+
+impl std::fmt::Display for Dump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}: step {}, ptr {}:",
+            self.line + 1,
+            self.pos + 1,
+            self.step,
+            self.ptr
+        )?;
+        for (addr, v) in (self.start..).zip(&self.cells) {
+            if addr == self.ptr {
+                write!(f, " [{v}]")?;
+            } else {
+                write!(f, " {v}")?;
             }
         }
+        Ok(())
+    }
+}
+
+/// Cell access made by a step, identified by its address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellAccess {
+    /// Read by `.`, `[` or `]`.
+    Read(isize),
+    /// Written by `+`, `-` or `,`.
+    Write(isize),
+}
+
+impl CellAccess {
+    pub fn addr(&self) -> isize {
+        match *self {
+            CellAccess::Read(addr) | CellAccess::Write(addr) => addr,
+        }
     }
-    tokens
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeError {
+    CellOverflow {
+        line: usize,
+        pos: usize,
+        step: usize,
+    },
+    CellUnderflow {
+        line: usize,
+        pos: usize,
+        step: usize,
+    },
+    PointerOutOfRange {
+        line: usize,
+        pos: usize,
+        step: usize,
+    },
+    UnmatchedBracket {
+        line: usize,
+        pos: usize,
+        step: usize,
+    },
+}
+
+impl RuntimeError {
+    pub fn line_and_pos(&self) -> (usize, usize) {
+        match *self {
+            RuntimeError::CellOverflow { line, pos, .. }
+            | RuntimeError::CellUnderflow { line, pos, .. }
+            | RuntimeError::PointerOutOfRange { line, pos, .. }
+            | RuntimeError::UnmatchedBracket { line, pos, .. } => (line, pos),
+        }
+    }
+
+    pub fn step(&self) -> usize {
+        match *self {
+            RuntimeError::CellOverflow { step, .. }
+            | RuntimeError::CellUnderflow { step, .. }
+            | RuntimeError::PointerOutOfRange { step, .. }
+            | RuntimeError::UnmatchedBracket { step, .. } => step,
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            RuntimeError::CellOverflow { .. } => "cell overflow",
+            RuntimeError::CellUnderflow { .. } => "cell underflow",
+            RuntimeError::PointerOutOfRange { .. } => "pointer out of range",
+            RuntimeError::UnmatchedBracket { .. } => "unmatched bracket",
+        }
+    }
+}
+
+impl std::fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (line, pos) = self.line_and_pos();
+        write!(
+            f,
+            "{} at line {}, column {} (step {})",
+            self.message(),
+            line + 1,
+            pos + 1,
+            self.step()
+        )
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnostic {
+    UnmatchedOpenBracket { line: usize, pos: usize },
+    UnmatchedCloseBracket { line: usize, pos: usize },
+}
+
+impl Diagnostic {
+    pub fn line_and_pos(&self) -> (usize, usize) {
+        match *self {
+            Diagnostic::UnmatchedOpenBracket { line, pos }
+            | Diagnostic::UnmatchedCloseBracket { line, pos } => (line, pos),
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Diagnostic::UnmatchedOpenBracket { .. } => "unmatched '['",
+            Diagnostic::UnmatchedCloseBracket { .. } => "unmatched ']'",
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (line, pos) = self.line_and_pos();
+        write!(f, "{}:{}: {}", line + 1, pos + 1, self.message())
+    }
+}
+
+pub fn check(source: &str, dialect: &Dialect) -> Vec<Diagnostic> {
+    validate(&tokenize(source, dialect))
+}
+
+fn initial_tape_len(tape_size: TapeSize) -> usize {
+    match tape_size {
+        TapeSize::Fixed(n) => n,
+        TapeSize::Infinite => INFINITE_TAPE_CHUNK,
+    }
+}
+
+/// Returns the positions of the commands marked with a `#` debug marker.
+///
+/// The command must follow the `#` on the same line, optionally separated by spaces,
+/// so that `#` in comments such as `Cell #1` is not treated as a marker. There are no markers with
+/// the dump extension, where `#` is a command that pauses by itself.
+pub fn breakpoint_markers(source: &str, dialect: &Dialect) -> Vec<(usize, usize)> {
+    if dialect.has_extension(Extension::Dump) {
+        return Vec::new();
+    }
+    let lines: Vec<Vec<char>> = source.lines().map(|s| s.chars().collect()).collect();
+    dialect
+        .commands(source)
+        .into_iter()
+        .map(|c| c.start)
+        .filter(|&(line, pos)| {
+            let before = lines[line][..pos].iter().rev();
+            let mut before = before.skip_while(|c| **c == ' ' || **c == '\t');
+            before.next() == Some(&'#')
+        })
+        .collect()
+}
+
+fn tokenize(source: &str, dialect: &Dialect) -> Vec<Token> {
+    dialect
+        .commands(source)
+        .into_iter()
+        .filter_map(|c| {
+            let tp = char_to_token_type(c.command)?;
+            let (line, pos) = c.start;
+            Some(Token::new(tp, line, pos, c.end))
+        })
+        .collect()
 }
 
 fn char_to_token_type(c: char) -> Option<TokenType> {
@@ -158,24 +931,26 @@ fn char_to_token_type(c: char) -> Option<TokenType> {
         ']' => Some(TokenType::RightSquare),
         '.' => Some(TokenType::Dot),
         ',' => Some(TokenType::Comma),
+        '#' => Some(TokenType::Hash),
         _ => None,
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 struct Token {
     tp: TokenType,
     line: usize,
     pos: usize,
+    end: (usize, usize), // position of the last character of the lexeme
 }
 
 impl Token {
-    fn new(tp: TokenType, line: usize, pos: usize) -> Token {
-        Token { tp, line, pos }
+    fn new(tp: TokenType, line: usize, pos: usize, end: (usize, usize)) -> Token {
+        Token { tp, line, pos, end }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum TokenType {
     Plus,
     Minus,
@@ -185,26 +960,69 @@ enum TokenType {
     RightSquare,
     Dot,
     Comma,
+    Hash,
 }
 
-fn build_jump_map(tokens: &[Token]) -> HashMap<Token, usize> {
-    let mut m = HashMap::new();
-    let mut stack: Vec<(&Token, usize)> = Vec::new();
+impl TokenType {
+    fn command(&self) -> char {
+        match self {
+            TokenType::Plus => '+',
+            TokenType::Minus => '-',
+            TokenType::RightAngle => '>',
+            TokenType::LeftAngle => '<',
+            TokenType::LeftSquare => '[',
+            TokenType::RightSquare => ']',
+            TokenType::Dot => '.',
+            TokenType::Comma => ',',
+            TokenType::Hash => '#',
+        }
+    }
+}
+
+/// Returns the index of the token following the matching bracket for each bracket token.
+fn build_jump_targets(tokens: &[Token]) -> Vec<Option<usize>> {
+    let mut targets = vec![None; tokens.len()];
+    let mut stack: Vec<usize> = Vec::new();
     for (i, t) in tokens.iter().enumerate() {
         match t.tp {
             TokenType::LeftSquare => {
-                stack.push((t, i));
+                stack.push(i);
             }
             TokenType::RightSquare => {
-                if let Some((tt, ii)) = stack.pop() {
-                    m.insert(*t, ii + 1);
-                    m.insert(*tt, i + 1);
+                if let Some(ii) = stack.pop() {
+                    targets[i] = Some(ii + 1);
+                    targets[ii] = Some(i + 1);
                 }
             }
             _ => {}
         }
     }
-    m
+    targets
+}
+
+fn validate(tokens: &[Token]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut stack: Vec<&Token> = Vec::new();
+    for t in tokens {
+        match t.tp {
+            TokenType::LeftSquare => {
+                stack.push(t);
+            }
+            TokenType::RightSquare if stack.pop().is_none() => {
+                diagnostics.push(Diagnostic::UnmatchedCloseBracket {
+                    line: t.line,
+                    pos: t.pos,
+                });
+            }
+            _ => {}
+        }
+    }
+    diagnostics.extend(stack.iter().map(|t| Diagnostic::UnmatchedOpenBracket {
+        line: t.line,
+        pos: t.pos,
+    }));
+    diagnostics.sort_by_key(|d| d.line_and_pos());
+    diagnostics
 }
 
 #[cfg(test)]
@@ -219,41 +1037,526 @@ abc .,
 あいうえお
 +- # foobar <>
 ";
-        let actual = tokenize(source);
+        let actual = tokenize(source, &Dialect::default());
+        let token = |tp, line, pos| Token::new(tp, line, pos, (line, pos));
         let expected = vec![
-            Token::new(TokenType::LeftAngle, 1, 0),
-            Token::new(TokenType::RightAngle, 1, 1),
-            Token::new(TokenType::LeftSquare, 1, 2),
-            Token::new(TokenType::RightSquare, 1, 3),
-            Token::new(TokenType::Dot, 2, 4),
-            Token::new(TokenType::Comma, 2, 5),
-            Token::new(TokenType::Plus, 4, 0),
-            Token::new(TokenType::Minus, 4, 1),
-            Token::new(TokenType::LeftAngle, 4, 12),
-            Token::new(TokenType::RightAngle, 4, 13),
+            token(TokenType::LeftAngle, 1, 0),
+            token(TokenType::RightAngle, 1, 1),
+            token(TokenType::LeftSquare, 1, 2),
+            token(TokenType::RightSquare, 1, 3),
+            token(TokenType::Dot, 2, 4),
+            token(TokenType::Comma, 2, 5),
+            token(TokenType::Plus, 4, 0),
+            token(TokenType::Minus, 4, 1),
+            token(TokenType::LeftAngle, 4, 12),
+            token(TokenType::RightAngle, 4, 13),
         ];
         assert_eq!(actual, expected);
+
+        let ook = Dialect::new(dialect::DialectName::Ook);
+        let actual = tokenize("Ook. Ook? Ook.\n Ook. # Ook! Ook?", &ook);
+        let expected = vec![
+            Token::new(TokenType::RightAngle, 0, 0, (0, 8)),
+            Token::new(TokenType::Plus, 0, 10, (1, 4)),
+            Token::new(TokenType::LeftSquare, 1, 8, (1, 16)),
+        ];
+        assert_eq!(actual, expected);
+        assert_eq!(
+            breakpoint_markers("Ook. Ook? Ook.\n Ook. # Ook! Ook?", &ook),
+            vec![(1, 8)]
+        );
+    }
+
+    #[test]
+    fn test_breakpoint_markers() {
+        let source = "
++#+ # comment
+>
+#
+#<
+# -   Cell #1
+";
+        let actual = breakpoint_markers(source, &Dialect::default());
+        let expected = vec![(1, 2), (4, 1), (5, 2)];
+        assert_eq!(actual, expected);
     }
 
     #[test]
     fn test_interpreter_hello_world() {
         let source = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
-        let output = run_interpreter(source, "");
-        assert_eq!(output, "Hello World!\n")
+        let output = run_interpreter(source, b"");
+        assert_eq!(output, b"Hello World!\n")
     }
 
     #[test]
     fn test_interpreter_echo() {
         let source = ",[.,]";
-        let output = run_interpreter(source, "Rust");
-        assert_eq!(output, "Rust")
+        let output = run_interpreter(source, b"Rust");
+        assert_eq!(output, b"Rust")
+    }
+
+    #[test]
+    fn test_interpreter_interactive_input() {
+        let source = ",.,.,.";
+        let mut interpreter =
+            Interpreter::new(source, b"a", Config::default(), &Dialect::default())
+                .with_interactive_input();
+        assert_eq!(interpreter.run(100, |_| false), Ok(RunOutcome::NeedsInput));
+        assert_eq!(interpreter.total_step_count(), 2);
+        assert_eq!(interpreter.step(), Ok(StepOutcome::NeedsInput));
+        assert_eq!(interpreter.total_step_count(), 2);
+
+        interpreter.push_input(b"b");
+        assert_eq!(
+            interpreter.run_fast(100, &[], |_| false),
+            Ok(RunOutcome::NeedsInput)
+        );
+        assert_eq!(interpreter.output(), b"ab");
+
+        interpreter.close_input();
+        assert_eq!(interpreter.run(100, |_| false), Ok(RunOutcome::Halted));
+        assert_eq!(interpreter.output(), b"ab\0");
+
+        assert!(interpreter.step_back());
+        assert!(interpreter.step_back());
+        assert_eq!(interpreter.step(), Ok(StepOutcome::Executed));
+        assert_eq!(interpreter.cell(0), 0);
+    }
+
+    #[test]
+    fn test_interpreter_dump() {
+        let dialect = Dialect::default()
+            .with_extensions(&[Extension::Dump, Extension::Separator])
+            .unwrap();
+        let source = "++>+++#\n<[->+<]>#!,";
+        let mut interpreter = Interpreter::new(source, b"", Config::default(), &dialect);
+        assert_eq!(interpreter.run(100, |_| false), Ok(RunOutcome::Dumped));
+        let dump = interpreter.dump();
+        assert_eq!(dump.cells, vec![2, 3]);
+        assert_eq!(dump.to_string(), "1:7: step 7, ptr 1: 2 [3]");
+
+        assert_eq!(
+            interpreter.run_fast(100, &[], |_| false),
+            Ok(RunOutcome::Dumped)
+        );
+        assert_eq!(interpreter.dump().to_string(), "2:9: step 21, ptr 1: 0 [5]");
+        // the input following `!` is not a part of the program
+        assert_eq!(interpreter.run(100, |_| false), Ok(RunOutcome::Halted));
+        assert!(interpreter.step_back());
+        assert_eq!(interpreter.step(), Ok(StepOutcome::Dumped));
+    }
+
+    #[test]
+    fn test_interpreter_multibyte_input() {
+        let mut interpreter = Interpreter::new(
+            ",.,.,.",
+            "あ".as_bytes(),
+            Config::default(),
+            &Dialect::default(),
+        );
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.cell(0), 0xE3);
+        assert_eq!(interpreter.input(), b"\x81\x82");
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.output(), "あ".as_bytes());
+        assert_eq!(interpreter.input(), b"");
+
+        while interpreter.step_back() {}
+        assert_eq!(interpreter.input(), "あ".as_bytes());
+        assert_eq!(interpreter.output(), b"");
+    }
+
+    #[test]
+    fn test_interpreter_runtime_errors() {
+        let err = run_interpreter_err("+\n>-");
+        assert_eq!(
+            err,
+            RuntimeError::CellUnderflow {
+                line: 1,
+                pos: 1,
+                step: 3
+            }
+        );
+
+        let err = run_interpreter_err(&"+".repeat(256));
+        assert_eq!(
+            err,
+            RuntimeError::CellOverflow {
+                line: 0,
+                pos: 255,
+                step: 256
+            }
+        );
+
+        let err = run_interpreter_err("><<");
+        assert_eq!(
+            err,
+            RuntimeError::PointerOutOfRange {
+                line: 0,
+                pos: 2,
+                step: 3
+            }
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let source = "
+]+[
+[-]]
+[[
+";
+        let actual = check(source, &Dialect::default());
+        let expected = vec![
+            Diagnostic::UnmatchedCloseBracket { line: 1, pos: 0 },
+            Diagnostic::UnmatchedOpenBracket { line: 3, pos: 0 },
+            Diagnostic::UnmatchedOpenBracket { line: 3, pos: 1 },
+        ];
+        assert_eq!(actual, expected);
+
+        assert!(check("+[>[-]<]", &Dialect::default()).is_empty());
+    }
+
+    #[test]
+    fn test_interpreter_cell_size() {
+        use config::CellSize;
+
+        let config = Config {
+            cell_size: CellSize::U16,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::new(&"+".repeat(300), b"", config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.memory()[0], 300);
+
+        let config = Config {
+            cell_size: CellSize::I8,
+            ..Default::default()
+        };
+        let err = run_interpreter_err_with(&"-".repeat(129), config);
+        assert_eq!(
+            err,
+            RuntimeError::CellUnderflow {
+                line: 0,
+                pos: 128,
+                step: 129
+            }
+        );
+    }
+
+    #[test]
+    fn test_interpreter_overflow_policy() {
+        use config::OverflowPolicy;
+
+        // [-] on a cell decremented from zero only terminates with wrapping cells
+        let config = Config {
+            cell_overflow: OverflowPolicy::Wrap,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::new("-[-]-", b"", config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.memory()[0], 255);
+
+        let config = Config {
+            cell_overflow: OverflowPolicy::Saturate,
+            pointer_overflow: OverflowPolicy::Wrap,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::new("<--<+++", b"", config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.current_ptr(), 29998);
+        assert_eq!(interpreter.memory()[interpreter.memory().len() - 1], 0);
+        assert_eq!(interpreter.memory()[interpreter.memory().len() - 2], 3);
+
+        let config = Config {
+            pointer_overflow: OverflowPolicy::Saturate,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::new("<<+", b"", config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        assert_eq!(interpreter.current_ptr(), 0);
+        assert_eq!(interpreter.memory()[0], 1);
+    }
+
+    #[test]
+    fn test_interpreter_tape_size() {
+        let config = Config {
+            tape_size: TapeSize::Fixed(3),
+            ..Default::default()
+        };
+        let err = run_interpreter_err_with(">>+>", config);
+        assert_eq!(
+            err,
+            RuntimeError::PointerOutOfRange {
+                line: 0,
+                pos: 3,
+                step: 4
+            }
+        );
+
+        let config = Config {
+            tape_size: TapeSize::Infinite,
+            ..Default::default()
+        };
+        let source = format!("<<<+{}++", ">".repeat(300));
+        let mut interpreter = Interpreter::new(&source, b"", config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
+        }
+        let start = interpreter.memory_start();
+        assert!(start <= -3);
+        assert_eq!(interpreter.current_ptr(), 297);
+        assert_eq!(interpreter.memory()[(-3 - start) as usize], 1);
+        assert_eq!(interpreter.memory()[(297 - start) as usize], 2);
+    }
+
+    #[test]
+    fn test_interpreter_eof_policy() {
+        let config = Config {
+            eof: EofPolicy::Zero,
+            ..Default::default()
+        };
+        assert_eq!(run_interpreter_with(",[.,]", b"Rust", config), b"Rust");
+        assert_eq!(run_interpreter_with("+,.", b"", config), b"\0");
+
+        let config = Config {
+            eof: EofPolicy::MinusOne,
+            cell_overflow: config::OverflowPolicy::Wrap,
+            ..Default::default()
+        };
+        assert_eq!(run_interpreter_with(",+[-.,+]", b"Rust", config), b"Rust");
+        assert_eq!(run_interpreter_with("+,.", b"", config), b"\xFF");
+
+        let config = Config {
+            eof: EofPolicy::Unchanged,
+            ..Default::default()
+        };
+        assert_eq!(run_interpreter_with(",[.[-],]", b"Rust", config), b"Rust");
+        assert_eq!(run_interpreter_with("+,.", b"", config), b"\x01");
+
+        let config = Config {
+            cell_size: config::CellSize::I16,
+            eof: EofPolicy::MinusOne,
+            ..Default::default()
+        };
+        let mut interpreter = Interpreter::new(",", b"", config, &Dialect::default());
+        interpreter.step().unwrap();
+        assert_eq!(interpreter.memory()[0], -1);
+    }
+
+    #[test]
+    fn test_interpreter_step_back() {
+        let source = ",[.,]";
+        let mut interpreter =
+            Interpreter::new(source, b"ab", Config::default(), &Dialect::default());
+        let mut states = Vec::new();
+        while !interpreter.end() {
+            states.push(snapshot(&interpreter));
+            interpreter.step().unwrap();
+        }
+        while let Some(state) = states.pop() {
+            assert!(interpreter.step_back());
+            assert_eq!(snapshot(&interpreter), state);
+        }
+        assert!(interpreter.counts.iter().all(|count| *count == 0));
+        assert!(!interpreter.step_back());
+    }
+
+    #[test]
+    fn test_interpreter_step_back_beyond_undo_entries() {
+        let config = Config {
+            cell_overflow: config::OverflowPolicy::Wrap,
+            ..Default::default()
+        };
+        let source = "+[>+[+]<+]";
+        let mut interpreter = Interpreter::new(source, b"", config, &Dialect::default());
+        let mut states = Vec::new();
+        for _ in 0..30_000 {
+            states.push(snapshot(&interpreter));
+            interpreter.step().unwrap();
+        }
+        for _ in 0..15_000 {
+            let state = states.pop().unwrap();
+            assert!(interpreter.step_back());
+            assert_eq!(snapshot(&interpreter), state);
+        }
     }
 
-    fn run_interpreter(source: &str, input: &str) -> String {
-        let mut interpreter = Interpreter::new(source, input);
+    #[test]
+    fn test_interpreter_last_access() {
+        let mut interpreter =
+            Interpreter::new("+>,.<[-]", b"a", Config::default(), &Dialect::default());
+        let mut accesses = Vec::new();
         while !interpreter.end() {
-            interpreter.step()
+            interpreter.step().unwrap();
+            accesses.push(interpreter.last_access());
+        }
+        let expected = vec![
+            Some(CellAccess::Write(0)),
+            None,
+            Some(CellAccess::Write(1)),
+            Some(CellAccess::Read(1)),
+            None,
+            Some(CellAccess::Read(0)),
+            Some(CellAccess::Write(0)),
+            Some(CellAccess::Read(0)),
+        ];
+        assert_eq!(accesses, expected);
+        assert_eq!(interpreter.cell(1), 97);
+        assert!(interpreter.step_back());
+        assert_eq!(interpreter.last_access(), None);
+    }
+
+    #[test]
+    fn test_interpreter_run_fast() {
+        use config::{CellSize, OverflowPolicy::*};
+
+        let sources = [
+            include_str!("../bf/hello.bf"),
+            include_str!("../bf/hello2.bf"),
+            "++++++[>++++++++<-]>.,[->+>++<<]>>[-]<.",
+            "+++++[>+++++[>++<-]<-]>>[<+>-]<.",
+            "-[>+>-<<-]>[-]>[+]+++[[-]>]",
+            ">>+++<<-[>>[->+<]<<+]>>>.",
+            "+[<<+>>-]<<[[+]-]",
+            "+++[>++++++++++++++++++++++++++++++++++++++++++++++<-]>>.",
+            ">+[-<<>>>+<]",
+        ];
+        let configs = [
+            Config::default(),
+            Config {
+                cell_overflow: Wrap,
+                ..Default::default()
+            },
+            Config {
+                cell_size: CellSize::I8,
+                cell_overflow: Wrap,
+                pointer_overflow: Wrap,
+                tape_size: TapeSize::Fixed(8),
+                ..Default::default()
+            },
+            Config {
+                cell_overflow: Saturate,
+                pointer_overflow: Saturate,
+                tape_size: TapeSize::Fixed(4),
+                ..Default::default()
+            },
+            Config {
+                cell_size: CellSize::I16,
+                tape_size: TapeSize::Infinite,
+                ..Default::default()
+            },
+        ];
+        for source in sources {
+            for config in configs {
+                let mut stepped = Interpreter::new(source, b"ab", config, &Dialect::default());
+                let mut stepped_result = Ok(RunOutcome::Running);
+                for _ in 0..1_000_000 {
+                    match stepped.step() {
+                        Ok(StepOutcome::Executed) => {}
+                        Ok(StepOutcome::NeedsInput | StepOutcome::Dumped) => unreachable!(),
+                        Ok(StepOutcome::Halted) => {
+                            stepped_result = Ok(RunOutcome::Halted);
+                            break;
+                        }
+                        Err(e) => {
+                            stepped_result = Err(e);
+                            break;
+                        }
+                    }
+                }
+                if stepped_result == Ok(RunOutcome::Running) {
+                    continue;
+                }
+
+                let mut fast = Interpreter::new(source, b"ab", config, &Dialect::default());
+                let mut fast_result = Ok(RunOutcome::Running);
+                while fast_result == Ok(RunOutcome::Running) {
+                    fast_result = fast.run_fast(1000, &[], |_| false);
+                }
+                let msg = format!("{source} with {config:?}");
+                assert_eq!(fast_result, stepped_result, "{msg}");
+                assert_eq!(fast.step_count, stepped.step_count, "{msg}");
+                assert_eq!(fast.ptr, stepped.ptr, "{msg}");
+                assert_eq!(fast.cur, stepped.cur, "{msg}");
+                assert_eq!(fast.memory(), stepped.memory(), "{msg}");
+                assert_eq!(fast.memory_start(), stepped.memory_start(), "{msg}");
+                assert_eq!(fast.output(), stepped.output(), "{msg}");
+                assert_eq!(fast.counts, stepped.counts, "{msg}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpreter_run_fast_pause() {
+        let source = "++++[>+++<-]>[-]+++++";
+        let mut interpreter = Interpreter::new(source, b"", Config::default(), &Dialect::default());
+        let outcome =
+            interpreter.run_fast(6, &[(0, 18)], |i| i.current_line_and_pos() == Some((0, 18)));
+        assert_eq!(outcome, Ok(RunOutcome::Paused));
+        assert_eq!(interpreter.cell(0), 0);
+        assert_eq!(interpreter.cell(1), 2);
+        assert_eq!(
+            interpreter.total_step_count(),
+            4 + (1 + 4 * 7) + 1 + (1 + 2 * 12) + 2
+        );
+
+        let mut states = Vec::new();
+        let mut stepped = Interpreter::new(source, b"", Config::default(), &Dialect::default());
+        while stepped.total_step_count() < interpreter.total_step_count() {
+            states.push(snapshot(&stepped));
+            stepped.step().unwrap();
+        }
+        while let Some(state) = states.pop() {
+            assert!(interpreter.step_back());
+            assert_eq!(snapshot(&interpreter), state);
+        }
+        assert!(interpreter.counts.iter().all(|count| *count == 0));
+    }
+
+    fn snapshot(interpreter: &Interpreter) -> (usize, isize, usize, Vec<i64>, Vec<u8>, Vec<u8>) {
+        (
+            interpreter.cur,
+            interpreter.ptr,
+            interpreter.step_count,
+            interpreter.memory[..4].to_vec(),
+            interpreter.input().to_vec(),
+            interpreter.output.clone(),
+        )
+    }
+
+    fn run_interpreter(source: &str, input: &[u8]) -> Vec<u8> {
+        run_interpreter_with(source, input, Config::default())
+    }
+
+    fn run_interpreter_with(source: &str, input: &[u8], config: Config) -> Vec<u8> {
+        let mut interpreter = Interpreter::new(source, input, config, &Dialect::default());
+        while !interpreter.end() {
+            interpreter.step().unwrap();
         }
         interpreter.output
     }
+
+    fn run_interpreter_err(source: &str) -> RuntimeError {
+        run_interpreter_err_with(source, Config::default())
+    }
+
+    fn run_interpreter_err_with(source: &str, config: Config) -> RuntimeError {
+        let mut interpreter = Interpreter::new(source, b"", config, &Dialect::default());
+        loop {
+            if let Err(e) = interpreter.step() {
+                return e;
+            }
+        }
+    }
 }
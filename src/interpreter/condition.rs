@@ -0,0 +1,410 @@
+use std::{fmt, str::FromStr};
+
+use super::Interpreter;
+
+/// Boolean expression over the interpreter state, used by conditional breakpoints.
+///
+/// Values are integers, and comparisons and logical operators yield 1 or 0:
+///
+/// ```text
+/// mem[3] == 0 && ptr > 10
+/// step > 5000 || output.len() >= 12
+/// mem[ptr + 1] != mem[ptr] && input.len() == 0
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn eval(&self, interpreter: &Interpreter) -> bool {
+        self.expr.eval(interpreter) != 0
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tokens = lex(s)?;
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            end: s.chars().count(),
+        };
+        let expr = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.unexpected(token));
+        }
+        Ok(Condition {
+            source: s.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Var(Var),
+    Mem(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Var {
+    Ptr,
+    Step,
+    OutputLen,
+    InputLen,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnaryOp {
+    Neg,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    And,
+    Or,
+}
+
+impl Expr {
+    fn eval(&self, interpreter: &Interpreter) -> i64 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var(Var::Ptr) => interpreter.current_ptr() as i64,
+            Expr::Var(Var::Step) => interpreter.total_step_count() as i64,
+            Expr::Var(Var::OutputLen) => interpreter.output().len() as i64,
+            Expr::Var(Var::InputLen) => interpreter.input().len() as i64,
+            Expr::Mem(addr) => interpreter.cell(addr.eval(interpreter) as isize),
+            Expr::Unary(UnaryOp::Neg, e) => e.eval(interpreter).wrapping_neg(),
+            Expr::Unary(UnaryOp::Not, e) => (e.eval(interpreter) == 0) as i64,
+            Expr::Binary(BinaryOp::And, l, r) => {
+                (l.eval(interpreter) != 0 && r.eval(interpreter) != 0) as i64
+            }
+            Expr::Binary(BinaryOp::Or, l, r) => {
+                (l.eval(interpreter) != 0 || r.eval(interpreter) != 0) as i64
+            }
+            Expr::Binary(op, l, r) => {
+                let (l, r) = (l.eval(interpreter), r.eval(interpreter));
+                match op {
+                    BinaryOp::Add => l.wrapping_add(r),
+                    BinaryOp::Sub => l.wrapping_sub(r),
+                    BinaryOp::Mul => l.wrapping_mul(r),
+                    BinaryOp::Eq => (l == r) as i64,
+                    BinaryOp::Ne => (l != r) as i64,
+                    BinaryOp::Lt => (l < r) as i64,
+                    BinaryOp::Le => (l <= r) as i64,
+                    BinaryOp::Gt => (l > r) as i64,
+                    BinaryOp::Ge => (l >= r) as i64,
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    Num(i64),
+    Ident(String),
+    Symbol(&'static str),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    kind: TokenKind,
+    pos: usize,
+}
+
+const SYMBOLS: [&str; 17] = [
+    "==", "!=", "<=", ">=", "&&", "||", "<", ">", "+", "-", "*", "!", "(", ")", "[", "]", ".",
+];
+
+fn lex(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let kind = if c.is_ascii_digit() {
+            while i < chars.len() && chars[i].is_ascii_digit() {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let n = digits
+                .parse()
+                .map_err(|_| format!("column {}: number too large", start + 1))?;
+            TokenKind::Num(n)
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            TokenKind::Ident(chars[start..i].iter().collect())
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|sym| sym.chars().eq(chars[i..].iter().take(sym.len()).copied()))
+                .ok_or_else(|| format!("column {}: unexpected character `{c}`", start + 1))?;
+            i += symbol.len();
+            TokenKind::Symbol(symbol)
+        };
+        tokens.push(Token { kind, pos: start });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_and()?;
+        while self.eat("||") {
+            let rhs = self.parse_and()?;
+            expr = Expr::Binary(BinaryOp::Or, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_comparison()?;
+        while self.eat("&&") {
+            let rhs = self.parse_comparison()?;
+            expr = Expr::Binary(BinaryOp::And, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expr, String> {
+        let expr = self.parse_sum()?;
+        let op = [
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::Ne),
+            ("<=", BinaryOp::Le),
+            (">=", BinaryOp::Ge),
+            ("<", BinaryOp::Lt),
+            (">", BinaryOp::Gt),
+        ]
+        .into_iter()
+        .find(|(sym, _)| self.eat(sym));
+        match op {
+            Some((_, op)) => {
+                let rhs = self.parse_sum()?;
+                Ok(Expr::Binary(op, Box::new(expr), Box::new(rhs)))
+            }
+            None => Ok(expr),
+        }
+    }
+
+    fn parse_sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_product()?;
+        loop {
+            let op = if self.eat("+") {
+                BinaryOp::Add
+            } else if self.eat("-") {
+                BinaryOp::Sub
+            } else {
+                return Ok(expr);
+            };
+            let rhs = self.parse_product()?;
+            expr = Expr::Binary(op, Box::new(expr), Box::new(rhs));
+        }
+    }
+
+    fn parse_product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.parse_unary()?;
+        while self.eat("*") {
+            let rhs = self.parse_unary()?;
+            expr = Expr::Binary(BinaryOp::Mul, Box::new(expr), Box::new(rhs));
+        }
+        Ok(expr)
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.eat("-") {
+            Ok(Expr::Unary(UnaryOp::Neg, Box::new(self.parse_unary()?)))
+        } else if self.eat("!") {
+            Ok(Expr::Unary(UnaryOp::Not, Box::new(self.parse_unary()?)))
+        } else {
+            self.parse_primary()
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        let Some(token) = self.next() else {
+            return Err(format!(
+                "column {}: unexpected end of condition",
+                self.end + 1
+            ));
+        };
+        match &token.kind {
+            TokenKind::Num(n) => Ok(Expr::Num(*n)),
+            TokenKind::Symbol("(") => {
+                let expr = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expr)
+            }
+            TokenKind::Ident(name) => match name.as_str() {
+                "ptr" => Ok(Expr::Var(Var::Ptr)),
+                "step" => Ok(Expr::Var(Var::Step)),
+                "true" => Ok(Expr::Num(1)),
+                "false" => Ok(Expr::Num(0)),
+                "mem" => {
+                    self.expect("[")?;
+                    let addr = self.parse_or()?;
+                    self.expect("]")?;
+                    Ok(Expr::Mem(Box::new(addr)))
+                }
+                "output" | "input" => {
+                    self.expect(".")?;
+                    match self.next() {
+                        Some(Token {
+                            kind: TokenKind::Ident(method),
+                            ..
+                        }) if method == "len" => {}
+                        Some(token) => return Err(self.unexpected(token)),
+                        None => {
+                            return Err(format!(
+                                "column {}: expected `len`, found end of condition",
+                                self.end + 1
+                            ));
+                        }
+                    }
+                    if self.eat("(") {
+                        self.expect(")")?;
+                    }
+                    Ok(Expr::Var(if name == "output" {
+                        Var::OutputLen
+                    } else {
+                        Var::InputLen
+                    }))
+                }
+                _ => Err(format!("column {}: unknown name `{name}`", token.pos + 1)),
+            },
+            TokenKind::Symbol(_) => Err(self.unexpected(token)),
+        }
+    }
+
+    fn peek(&self) -> Option<Token> {
+        self.tokens.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek();
+        if token.is_some() {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token {
+                kind: TokenKind::Symbol(s),
+                ..
+            }) if s == symbol => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect(&mut self, symbol: &str) -> Result<(), String> {
+        if self.eat(symbol) {
+            return Ok(());
+        }
+        match self.peek() {
+            Some(token) => Err(format!(
+                "column {}: expected `{symbol}`, found {}",
+                token.pos + 1,
+                describe(&token)
+            )),
+            None => Err(format!(
+                "column {}: expected `{symbol}`, found end of condition",
+                self.end + 1
+            )),
+        }
+    }
+
+    fn unexpected(&self, token: Token) -> String {
+        format!("column {}: unexpected {}", token.pos + 1, describe(&token))
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match &token.kind {
+        TokenKind::Num(n) => format!("`{n}`"),
+        TokenKind::Ident(name) => format!("`{name}`"),
+        TokenKind::Symbol(s) => format!("`{s}`"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{config::Config, dialect::Dialect};
+
+    #[test]
+    fn test_parse_error() {
+        let err = |s: &str| s.parse::<Condition>().unwrap_err();
+        assert_eq!(
+            err("mem[3 == 0"),
+            "column 11: expected `]`, found end of condition"
+        );
+        assert_eq!(err("foo > 1"), "column 1: unknown name `foo`");
+        assert_eq!(err("ptr > 1 )"), "column 9: unexpected `)`");
+        assert_eq!(err("ptr $ 1"), "column 5: unexpected character `$`");
+        assert_eq!(err("output.size()"), "column 8: unexpected `size`");
+        assert_eq!(err(""), "column 1: unexpected end of condition");
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut interpreter =
+            Interpreter::new("+++>++>+.", b"abc", Config::default(), &Dialect::default());
+        for _ in 0..8 {
+            interpreter.step().unwrap();
+        }
+        let eval = |s: &str, i: &Interpreter| s.parse::<Condition>().unwrap().eval(i);
+        assert!(eval("mem[0] == 3 && ptr == 2", &interpreter));
+        assert!(eval("mem[ptr - 1] == 2", &interpreter));
+        assert!(eval("mem[100] == 0", &interpreter));
+        assert!(eval("step >= 8 || false", &interpreter));
+        assert!(eval("output.len() == 0 && input.len == 3", &interpreter));
+        assert!(eval("!(ptr < 2) && -ptr * 2 == -4", &interpreter));
+        assert!(!eval("mem[1] != 2", &interpreter));
+        assert!(!eval("step > 5000", &interpreter));
+        interpreter.step().unwrap();
+        assert!(eval("output.len() >= 1", &interpreter));
+    }
+}
@@ -0,0 +1,195 @@
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Config {
+    pub cell_size: CellSize,
+    pub cell_overflow: OverflowPolicy,
+    pub pointer_overflow: OverflowPolicy,
+    pub tape_size: TapeSize,
+    pub eof: EofPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CellSize {
+    #[default]
+    U8,
+    U16,
+    U32,
+    I8,
+    I16,
+    I32,
+}
+
+impl CellSize {
+    pub fn bits(&self) -> u32 {
+        match self {
+            CellSize::U8 | CellSize::I8 => 8,
+            CellSize::U16 | CellSize::I16 => 16,
+            CellSize::U32 | CellSize::I32 => 32,
+        }
+    }
+
+    pub fn signed(&self) -> bool {
+        matches!(self, CellSize::I8 | CellSize::I16 | CellSize::I32)
+    }
+
+    pub fn min(&self) -> i64 {
+        if self.signed() {
+            -(1 << (self.bits() - 1))
+        } else {
+            0
+        }
+    }
+
+    pub fn max(&self) -> i64 {
+        if self.signed() {
+            (1 << (self.bits() - 1)) - 1
+        } else {
+            (1 << self.bits()) - 1
+        }
+    }
+
+    /// Number of hex digits needed to display a cell.
+    pub fn hex_width(&self) -> usize {
+        self.bits() as usize / 4
+    }
+
+    /// Returns the two's complement bit pattern of the value.
+    pub fn bit_pattern(&self, v: i64) -> u64 {
+        (v as u64) & (u64::MAX >> (64 - self.bits()))
+    }
+
+    /// Converts a byte read by `,` into a cell value.
+    /// The byte is sign-extended for 8-bit signed cells and zero-extended otherwise.
+    pub fn input_value(&self, b: u8) -> i64 {
+        match self {
+            CellSize::I8 => b as i8 as i64,
+            _ => b as i64,
+        }
+    }
+
+    /// Returns the value stored by `,` at EOF with [`EofPolicy::MinusOne`].
+    pub fn minus_one(&self) -> i64 {
+        if self.signed() { -1 } else { self.max() }
+    }
+
+    /// Converts a cell value into the byte written by `.`, keeping the lowest 8 bits.
+    pub fn output_byte(&self, v: i64) -> u8 {
+        self.bit_pattern(v) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OverflowPolicy {
+    #[default]
+    Error,
+    Wrap,
+    Saturate,
+}
+
+impl OverflowPolicy {
+    /// Fits the value into `min..=max`, or returns `None` if the policy rejects it.
+    pub fn apply(&self, v: i64, min: i64, max: i64) -> Option<i64> {
+        if (min..=max).contains(&v) {
+            return Some(v);
+        }
+        match self {
+            OverflowPolicy::Error => None,
+            OverflowPolicy::Wrap => Some(min + (v - min).rem_euclid(max - min + 1)),
+            OverflowPolicy::Saturate => Some(v.clamp(min, max)),
+        }
+    }
+}
+
+/// What `,` stores when the input is exhausted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum EofPolicy {
+    #[default]
+    Zero,
+    MinusOne,
+    Unchanged,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TapeSize {
+    Fixed(usize),
+    /// The tape grows on demand in both directions.
+    Infinite,
+}
+
+impl Default for TapeSize {
+    fn default() -> Self {
+        TapeSize::Fixed(30000)
+    }
+}
+
+impl FromStr for TapeSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "infinite" {
+            return Ok(TapeSize::Infinite);
+        }
+        match s.parse::<usize>() {
+            Ok(n) if n > 0 => Ok(TapeSize::Fixed(n)),
+            _ => Err(format!(
+                "invalid tape size: {s} (expected a positive number or `infinite`)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for TapeSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TapeSize::Fixed(n) => write!(f, "{n}"),
+            TapeSize::Infinite => write!(f, "infinite"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cell_size_range() {
+        assert_eq!((CellSize::U8.min(), CellSize::U8.max()), (0, 255));
+        assert_eq!((CellSize::U16.min(), CellSize::U16.max()), (0, 65535));
+        assert_eq!((CellSize::U32.min(), CellSize::U32.max()), (0, 4294967295));
+        assert_eq!((CellSize::I8.min(), CellSize::I8.max()), (-128, 127));
+        assert_eq!((CellSize::I16.min(), CellSize::I16.max()), (-32768, 32767));
+        assert_eq!(
+            (CellSize::I32.min(), CellSize::I32.max()),
+            (-2147483648, 2147483647)
+        );
+    }
+
+    #[test]
+    fn test_cell_size_bytes() {
+        assert_eq!(CellSize::I8.input_value(0xFF), -1);
+        assert_eq!(CellSize::I16.input_value(0xFF), 255);
+        assert_eq!(CellSize::I8.output_byte(-1), 0xFF);
+        assert_eq!(CellSize::U16.output_byte(0x1234), 0x34);
+        assert_eq!(CellSize::I16.bit_pattern(-1), 0xFFFF);
+    }
+
+    #[test]
+    fn test_overflow_policy_apply() {
+        assert_eq!(OverflowPolicy::Error.apply(256, 0, 255), None);
+        assert_eq!(OverflowPolicy::Error.apply(255, 0, 255), Some(255));
+        assert_eq!(OverflowPolicy::Wrap.apply(256, 0, 255), Some(0));
+        assert_eq!(OverflowPolicy::Wrap.apply(-1, 0, 255), Some(255));
+        assert_eq!(OverflowPolicy::Wrap.apply(128, -128, 127), Some(-128));
+        assert_eq!(OverflowPolicy::Saturate.apply(256, 0, 255), Some(255));
+        assert_eq!(OverflowPolicy::Saturate.apply(-1, 0, 255), Some(0));
+    }
+
+    #[test]
+    fn test_tape_size_from_str() {
+        assert_eq!("30000".parse(), Ok(TapeSize::Fixed(30000)));
+        assert_eq!("infinite".parse(), Ok(TapeSize::Infinite));
+        assert!("0".parse::<TapeSize>().is_err());
+        assert!("-1".parse::<TapeSize>().is_err());
+    }
+}
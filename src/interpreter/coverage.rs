@@ -0,0 +1,339 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::Deserialize;
+
+use super::profile::{CommandCount, Profile};
+
+/// Execution counts of the commands merged over several runs of the same program.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    commands: Vec<CommandCount>,
+    runs: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CoverageFormat {
+    #[default]
+    Lcov,
+    Json,
+}
+
+/// Fields of a JSON report read back for merging.
+#[derive(Deserialize)]
+struct JsonReport {
+    runs: usize,
+    commands: Vec<JsonCommand>,
+}
+
+#[derive(Deserialize)]
+struct JsonCommand {
+    command: char,
+    line: usize,
+    column: usize,
+    count: usize,
+}
+
+impl Coverage {
+    /// Coverage of a single run, which is not counted as a run if no step was executed.
+    pub fn from_profile(profile: &Profile) -> Coverage {
+        Coverage {
+            commands: profile.commands.clone(),
+            runs: usize::from(profile.total_steps > 0),
+        }
+    }
+
+    /// Adds the counts of the other coverage, which must have the same commands at the same
+    /// positions unless it has no commands at all. Nothing is merged if they do not match.
+    pub fn merge(&mut self, other: &Coverage) -> Result<(), String> {
+        let key = |c: &CommandCount| (c.command, c.start);
+        let keys = self.commands.iter().map(key);
+        if !other.commands.is_empty() && !keys.eq(other.commands.iter().map(key)) {
+            let pairs = self.commands.iter().zip(&other.commands);
+            let i = pairs
+                .clone()
+                .position(|(c, o)| key(c) != key(o))
+                .unwrap_or(pairs.len());
+            let (line, column) = other.commands.get(i).unwrap_or(&self.commands[i]).start;
+            return Err(format!(
+                "the commands do not match the source at line {}, column {}",
+                line + 1,
+                column + 1
+            ));
+        }
+        for (c, o) in self.commands.iter_mut().zip(&other.commands) {
+            c.count += o.count;
+        }
+        self.runs += other.runs;
+        Ok(())
+    }
+
+    pub fn commands(&self) -> &[CommandCount] {
+        &self.commands
+    }
+
+    pub fn runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Returns the number of commands executed at least once.
+    pub fn covered(&self) -> usize {
+        self.commands.iter().filter(|c| c.count > 0).count()
+    }
+
+    pub fn export(&self, source_file: &str, format: CoverageFormat) -> String {
+        match format {
+            CoverageFormat::Lcov => self.to_lcov(source_file),
+            CoverageFormat::Json => self.to_json(source_file),
+        }
+    }
+
+    /// Writes the report with the counts of an existing report in the same format added, so that
+    /// the runs with different inputs add up.
+    ///
+    /// An lcov report only has the counts of the lines, so they are added up line by line.
+    pub fn export_merged(
+        &self,
+        source_file: &str,
+        format: CoverageFormat,
+        existing: &str,
+    ) -> Result<String, String> {
+        match format {
+            CoverageFormat::Lcov => {
+                let mut lines = self.line_counts();
+                let existing = parse_lcov(existing)?;
+                let mismatch = lines
+                    .keys()
+                    .chain(existing.keys())
+                    .filter(|line| lines.contains_key(line) != existing.contains_key(line))
+                    .min();
+                if let Some(line) = mismatch.filter(|_| !existing.is_empty()) {
+                    return Err(format!(
+                        "the lines do not match the source at line {}",
+                        line + 1
+                    ));
+                }
+                for (line, count) in existing {
+                    *lines.entry(line).or_default() += count;
+                }
+                Ok(lcov_str(source_file, &lines))
+            }
+            CoverageFormat::Json => {
+                let mut merged = self.clone();
+                merged.merge(&Coverage::parse_json(existing)?)?;
+                Ok(merged.to_json(source_file))
+            }
+        }
+    }
+
+    /// Returns the count of each line, which is the smallest count of its commands, so that a
+    /// line is not covered unless all of its commands are.
+    fn line_counts(&self) -> BTreeMap<usize, usize> {
+        let mut lines: BTreeMap<usize, usize> = BTreeMap::new();
+        for c in &self.commands {
+            let count = lines.entry(c.start.0).or_insert(c.count);
+            *count = (*count).min(c.count);
+        }
+        lines
+    }
+
+    fn to_lcov(&self, source_file: &str) -> String {
+        lcov_str(source_file, &self.line_counts())
+    }
+
+    fn to_json(&self, source_file: &str) -> String {
+        let source_file = serde_json::Value::from(source_file);
+        let commands: Vec<String> = self
+            .commands
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"command\": \"{}\", \"line\": {}, \"column\": {}, \"count\": {}}}",
+                    c.command,
+                    c.start.0 + 1,
+                    c.start.1 + 1,
+                    c.count
+                )
+            })
+            .collect();
+        let commands = if commands.is_empty() {
+            String::new()
+        } else {
+            format!("\n    {}\n  ", commands.join(",\n    "))
+        };
+        format!(
+            "{{\n  \"source\": {source_file},\n  \"runs\": {},\n  \"covered\": {},\n  \"total\": {},\n  \"commands\": [{commands}]\n}}\n",
+            self.runs,
+            self.covered(),
+            self.commands.len()
+        )
+    }
+
+    /// Parses a JSON report written by `export`, so that it can be merged with another run.
+    ///
+    /// Only the runs and the commands are read, and the end positions of the commands are not
+    /// restored.
+    pub fn parse_json(content: &str) -> Result<Coverage, String> {
+        let report: JsonReport = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let mut commands = report
+            .commands
+            .iter()
+            .map(|c| {
+                let start = (c.line.checked_sub(1)?, c.column.checked_sub(1)?);
+                Some(CommandCount {
+                    command: c.command,
+                    start,
+                    end: start,
+                    count: c.count,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+            .ok_or("lines and columns start at 1")?;
+        commands.sort_by_key(|c| c.start);
+        Ok(Coverage {
+            commands,
+            runs: report.runs,
+        })
+    }
+}
+
+fn lcov_str(source_file: &str, lines: &BTreeMap<usize, usize>) -> String {
+    let mut lcov = format!("TN:\nSF:{source_file}\n");
+    for (line, count) in lines {
+        let _ = writeln!(lcov, "DA:{},{count}", line + 1);
+    }
+    let hit = lines.values().filter(|count| **count > 0).count();
+    let _ = write!(lcov, "LF:{}\nLH:{hit}\nend_of_record\n", lines.len());
+    lcov
+}
+
+/// Reads the line counts from the `DA:<line>,<count>` records of an lcov report.
+fn parse_lcov(content: &str) -> Result<BTreeMap<usize, usize>, String> {
+    let mut lines = BTreeMap::new();
+    for (i, record) in content.lines().enumerate() {
+        let Some(data) = record.trim().strip_prefix("DA:") else {
+            continue;
+        };
+        // an optional checksum may follow the count
+        let mut fields = data.split(',').map(|f| f.trim().parse::<usize>().ok());
+        let line = fields.next().flatten().and_then(|line| line.checked_sub(1));
+        let count = fields.next().flatten();
+        let (Some(line), Some(count)) = (line, count) else {
+            return Err(format!("invalid record on line {}: {record}", i + 1));
+        };
+        *lines.entry(line).or_default() += count;
+    }
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, config::Config, dialect::Dialect};
+
+    fn run(source: &str, input: &[u8]) -> Coverage {
+        let mut interpreter =
+            Interpreter::new(source, input, Config::default(), &Dialect::default());
+        interpreter.run(1000, |_| false).unwrap();
+        Coverage::from_profile(&interpreter.profile())
+    }
+
+    #[test]
+    fn test_coverage_merge() {
+        let source = ",[>+<-]\n[.]";
+        let mut coverage = run(source, b"\x01");
+        assert_eq!(coverage.covered(), 8);
+        let counts: Vec<usize> = coverage.commands().iter().map(|c| c.count).collect();
+        assert_eq!(counts, vec![1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+
+        coverage.merge(&run(source, b"\x02")).unwrap();
+        assert_eq!(coverage.runs(), 2);
+        let counts: Vec<usize> = coverage.commands().iter().map(|c| c.count).collect();
+        assert_eq!(counts, vec![2, 2, 3, 3, 3, 3, 3, 2, 0, 0]);
+
+        // a run without any step is not counted
+        let interpreter = Interpreter::new(source, b"", Config::default(), &Dialect::default());
+        coverage
+            .merge(&Coverage::from_profile(&interpreter.profile()))
+            .unwrap();
+        assert_eq!(coverage.runs(), 2);
+
+        // nothing is merged from another source
+        let before = coverage.clone();
+        let err = coverage.merge(&run(",[>+<-]\n [.]", b"")).unwrap_err();
+        assert_eq!(
+            err,
+            "the commands do not match the source at line 2, column 2"
+        );
+        let err = coverage.merge(&run(",[>+<-]", b"")).unwrap_err();
+        assert_eq!(
+            err,
+            "the commands do not match the source at line 2, column 1"
+        );
+        assert_eq!(coverage, before);
+        assert!(coverage.merge(&Coverage::default()).is_ok());
+    }
+
+    #[test]
+    fn test_coverage_export() {
+        let coverage = run(",[>+<-]\n[.]", b"\x01");
+        let lcov = coverage.export("a.bf", CoverageFormat::Lcov);
+        assert_eq!(
+            lcov,
+            "TN:\nSF:a.bf\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+
+        let json = coverage.export("a.bf", CoverageFormat::Json);
+        assert!(json.starts_with(
+            "{\n  \"source\": \"a.bf\",\n  \"runs\": 1,\n  \"covered\": 8,\n  \"total\": 10,\n  \"commands\": [\n    {\"command\": \",\", \"line\": 1, \"column\": 1, \"count\": 1},\n"
+        ));
+        let parsed = Coverage::parse_json(&json).unwrap();
+        assert_eq!(parsed.runs(), 1);
+        assert_eq!(parsed.covered(), 8);
+        assert_eq!(parsed.commands()[9].start, (1, 2));
+        assert_eq!(parsed.commands()[9].command, ']');
+
+        let merged = run(",[>+<-]\n[.]", b"");
+        let json = merged
+            .export_merged("a.bf", CoverageFormat::Json, &json)
+            .unwrap();
+        assert!(json.contains("\"runs\": 2,\n  \"covered\": 8,"));
+
+        // the layout of the report does not matter
+        let compact = r#"{"commands":[{"count":3,"column":1,"line":1,"command":"+"}],"runs":2}"#;
+        let parsed = Coverage::parse_json(compact).unwrap();
+        assert_eq!((parsed.runs(), parsed.commands()[0].count), (2, 3));
+        assert!(Coverage::parse_json(r#"{"runs": "x", "commands": []}"#).is_err());
+        let line_zero =
+            r#"{"runs": 1, "commands": [{"command": "+", "line": 0, "column": 1, "count": 1}]}"#;
+        assert!(Coverage::parse_json(line_zero).is_err());
+    }
+
+    #[test]
+    fn test_coverage_merge_lcov() {
+        let source = ",[>+<-]\n[.]";
+        let lcov = run(source, b"\x01").export("a.bf", CoverageFormat::Lcov);
+        let lcov = run(source, b"")
+            .export_merged("a.bf", CoverageFormat::Lcov, &lcov)
+            .unwrap();
+        assert_eq!(
+            lcov,
+            "TN:\nSF:a.bf\nDA:1,1\nDA:2,0\nLF:2\nLH:1\nend_of_record\n"
+        );
+        let lcov = run(source, b"\x02")
+            .export_merged("a.bf", CoverageFormat::Lcov, &lcov)
+            .unwrap();
+        assert!(lcov.contains("DA:1,2\nDA:2,0\n"));
+
+        // a report for another source is not overwritten
+        let err = run(",[>+<-]\n\n[.]", b"")
+            .export_merged("a.bf", CoverageFormat::Lcov, &lcov)
+            .unwrap_err();
+        assert_eq!(err, "the lines do not match the source at line 2");
+        let err = run(source, b"")
+            .export_merged("a.bf", CoverageFormat::Lcov, "TN:\nDA:1\n")
+            .unwrap_err();
+        assert_eq!(err, "invalid record on line 2: DA:1");
+        let empty = run(source, b"").export_merged("a.bf", CoverageFormat::Lcov, "");
+        assert!(empty.unwrap().contains("DA:1,0\nDA:2,0\n"));
+    }
+}
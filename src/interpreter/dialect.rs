@@ -0,0 +1,416 @@
+use serde::Deserialize;
+
+use crate::config_file::toml_error;
+
+/// The eight brainf*ck commands, in the order of the lexemes of a dialect.
+const COMMANDS: [char; 8] = ['+', '-', '>', '<', '[', ']', '.', ','];
+/// Keys of the commands in a dialect file, in the order of `COMMANDS`.
+const KEYS: [&str; 8] = [
+    "increment",
+    "decrement",
+    "right",
+    "left",
+    "loop_start",
+    "loop_end",
+    "output",
+    "input",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DialectName {
+    #[default]
+    #[value(alias = "bf")]
+    Brainfuck,
+    Ook,
+    Blub,
+}
+
+/// Optional commands that can be added to any dialect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Extension {
+    // `#` dumps the memory
+    Dump,
+    // `!` ends the program, and the rest of the source is used as the input
+    Separator,
+}
+
+impl Extension {
+    pub fn command(&self) -> char {
+        match self {
+            Extension::Dump => '#',
+            Extension::Separator => '!',
+        }
+    }
+}
+
+/// Mapping from the lexemes of a brainf*ck dialect to the commands they stand for.
+///
+/// A lexeme consists of one or more words, which may be separated by any whitespace in the
+/// source, including line breaks. Like in brainf*ck, everything that is not a lexeme is ignored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    lexemes: Vec<(Vec<String>, char)>, // sorted by length, so that the longest lexeme matches
+}
+
+/// Lexemes of the commands in a dialect file, with the keys in `KEYS`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DialectFile {
+    increment: String,
+    decrement: String,
+    right: String,
+    left: String,
+    loop_start: String,
+    loop_end: String,
+    output: String,
+    input: String,
+}
+
+/// Command found in the source, with the positions (line, column) of the first and last
+/// characters of its lexeme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Command {
+    pub command: char,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect::new(DialectName::Brainfuck)
+    }
+}
+
+impl Dialect {
+    pub fn new(name: DialectName) -> Dialect {
+        match name {
+            DialectName::Brainfuck => Dialect::from_lexemes(COMMANDS.map(String::from)),
+            DialectName::Ook => Dialect::ook_like("Ook"),
+            DialectName::Blub => Dialect::ook_like("Blub"),
+        }
+        .unwrap()
+    }
+
+    /// Dialect in which each command is a pair of `word.`, `word?` and `word!`, like Ook!.
+    fn ook_like(word: &str) -> Result<Dialect, String> {
+        let [dot, question, bang] = [".", "?", "!"].map(|p| format!("{word}{p}"));
+        Dialect::from_lexemes([
+            format!("{dot} {dot}"),
+            format!("{bang} {bang}"),
+            format!("{dot} {question}"),
+            format!("{question} {dot}"),
+            format!("{bang} {question}"),
+            format!("{question} {bang}"),
+            format!("{bang} {dot}"),
+            format!("{dot} {bang}"),
+        ])
+    }
+
+    /// Creates a dialect from the lexemes of the commands in the order of `COMMANDS`.
+    fn from_lexemes(lexemes: [String; 8]) -> Result<Dialect, String> {
+        let mut dialect = Dialect {
+            lexemes: Vec::with_capacity(lexemes.len()),
+        };
+        for (lexeme, (command, key)) in lexemes.iter().zip(COMMANDS.iter().zip(KEYS)) {
+            let words: Vec<String> = lexeme.split_whitespace().map(String::from).collect();
+            if words.is_empty() {
+                return Err(format!("empty lexeme for `{key}`"));
+            }
+            if dialect.lexemes.iter().any(|(w, _)| *w == words) {
+                return Err(format!("duplicate lexeme `{lexeme}` for `{key}`"));
+            }
+            dialect.lexemes.push((words, *command));
+        }
+        dialect.lexemes.sort_by_key(|(words, _)| {
+            std::cmp::Reverse(words.iter().map(String::len).sum::<usize>())
+        });
+        Ok(dialect)
+    }
+
+    /// Parses a TOML dialect file, which gives the lexemes of all eight commands:
+    ///
+    /// ```toml
+    /// increment = "Ook. Ook."
+    /// decrement = "Ook! Ook!"
+    /// right = "Ook. Ook?"
+    /// left = "Ook? Ook."
+    /// loop_start = "Ook! Ook?"
+    /// loop_end = "Ook? Ook!"
+    /// output = "Ook! Ook."
+    /// input = "Ook. Ook!"
+    /// ```
+    pub fn parse(content: &str) -> Result<Dialect, String> {
+        let file: DialectFile = toml::from_str(content).map_err(|e| toml_error(content, &e))?;
+        Dialect::from_lexemes([
+            file.increment,
+            file.decrement,
+            file.right,
+            file.left,
+            file.loop_start,
+            file.loop_end,
+            file.output,
+            file.input,
+        ])
+    }
+
+    /// Adds the commands of the extensions, whose lexemes are their single characters.
+    pub fn with_extensions(mut self, extensions: &[Extension]) -> Result<Dialect, String> {
+        for e in extensions {
+            let c = e.command();
+            if self.has_command(c) {
+                continue;
+            }
+            let words = vec![c.to_string()];
+            if self.lexemes.iter().any(|(w, _)| *w == words) {
+                return Err(format!("`{c}` is already a lexeme of the dialect"));
+            }
+            self.lexemes.push((words, c));
+        }
+        self.lexemes.sort_by_key(|(words, _)| {
+            std::cmp::Reverse(words.iter().map(String::len).sum::<usize>())
+        });
+        Ok(self)
+    }
+
+    pub fn has_extension(&self, extension: Extension) -> bool {
+        self.has_command(extension.command())
+    }
+
+    fn has_command(&self, command: char) -> bool {
+        self.lexemes.iter().any(|(_, c)| *c == command)
+    }
+
+    /// Splits the source after the first `!` with the separator extension, returning the program
+    /// including the `!` and the input following it.
+    pub fn split_input<'a>(&self, source: &'a str) -> (&'a str, Option<&'a str>) {
+        let separator = self.commands(source).pop().filter(|c| c.command == '!');
+        let Some(Command {
+            end: (line, pos), ..
+        }) = separator
+        else {
+            return (source, None);
+        };
+        let offset: usize = source.split_inclusive('\n').take(line).map(str::len).sum();
+        let rest = &source[offset..];
+        let len = rest
+            .char_indices()
+            .nth(pos + 1)
+            .map_or(rest.len(), |(i, _)| i);
+        let (program, input) = source.split_at(offset + len);
+        (program, Some(input))
+    }
+
+    /// Returns the commands in the source in order, up to the first `!` with the separator
+    /// extension.
+    pub fn commands(&self, source: &str) -> Vec<Command> {
+        // line breaks are kept so that words of a lexeme can be on different lines
+        let chars: Vec<((usize, usize), char)> = source
+            .lines()
+            .enumerate()
+            .flat_map(|(line, s)| {
+                let eol = ((line, s.chars().count()), '\n');
+                s.chars()
+                    .enumerate()
+                    .map(move |(pos, c)| ((line, pos), c))
+                    .chain(std::iter::once(eol))
+            })
+            .collect();
+
+        let mut commands = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let found = self
+                .lexemes
+                .iter()
+                .find_map(|(words, command)| {
+                    match_words(&chars[i..], words).map(|len| (len, *command))
+                })
+                // the command of an extension is not matched inside a word of the dialect
+                .filter(|(_, command)| COMMANDS.contains(command) || !self.in_word(&chars, i));
+            match found {
+                Some((len, command)) => {
+                    commands.push(Command {
+                        command,
+                        start: chars[i].0,
+                        end: chars[i + len - 1].0,
+                    });
+                    i += len;
+                    if command == '!' {
+                        break;
+                    }
+                }
+                None => i += 1,
+            }
+        }
+        commands
+    }
+
+    /// Returns whether the character at `i` belongs to a word of the lexemes that is longer than a
+    /// character, such as the `!` of `Ook!`.
+    fn in_word(&self, chars: &[((usize, usize), char)], i: usize) -> bool {
+        let words = self.lexemes.iter().flat_map(|(words, _)| words);
+        words.filter(|w| w.chars().count() > 1).any(|word| {
+            let mut offsets = word.chars().enumerate().filter(|(_, c)| *c == chars[i].1);
+            offsets.any(|(k, _)| {
+                i >= k && match_words(&chars[i - k..], std::slice::from_ref(word)).is_some()
+            })
+        })
+    }
+
+    /// Returns the lexeme of the command, with the words separated by a space.
+    pub fn lexeme(&self, command: char) -> String {
+        let (words, _) = self.lexemes.iter().find(|(_, c)| *c == command).unwrap();
+        words.join(" ")
+    }
+
+    /// Returns the whitespace put between lexemes on the same line: none if all lexemes are
+    /// single characters, like in brainf*ck.
+    pub fn separator(&self) -> &'static str {
+        let single = |words: &Vec<String>| words.len() == 1 && words[0].chars().count() == 1;
+        if self.lexemes.iter().all(|(words, _)| single(words)) {
+            ""
+        } else {
+            " "
+        }
+    }
+}
+
+/// Returns the number of characters matched by the words separated by whitespace.
+fn match_words(chars: &[((usize, usize), char)], words: &[String]) -> Option<usize> {
+    let mut n = 0;
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            let spaces = chars[n..].iter().take_while(|(_, c)| c.is_whitespace());
+            match spaces.count() {
+                0 => return None,
+                count => n += count,
+            }
+        }
+        for w in word.chars() {
+            if chars.get(n).map(|(_, c)| *c) != Some(w) {
+                return None;
+            }
+            n += 1;
+        }
+    }
+    Some(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(command: char, start: (usize, usize), end: (usize, usize)) -> Command {
+        Command {
+            command,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_dialect_commands() {
+        let brainfuck = Dialect::default();
+        let actual = brainfuck.commands("+ a\n[-]");
+        let expected = vec![
+            command('+', (0, 0), (0, 0)),
+            command('[', (1, 0), (1, 0)),
+            command('-', (1, 1), (1, 1)),
+            command(']', (1, 2), (1, 2)),
+        ];
+        assert_eq!(actual, expected);
+
+        let ook = Dialect::new(DialectName::Ook);
+        let actual = ook.commands("Ook. Ook? Ook.\n  Ook. Ook!Ook! Ook! Ook!");
+        let expected = vec![
+            command('>', (0, 0), (0, 8)),
+            command('+', (0, 10), (1, 5)),
+            command('-', (1, 11), (1, 19)),
+        ];
+        assert_eq!(actual, expected);
+
+        let blub = Dialect::new(DialectName::Blub);
+        assert_eq!(blub.commands("Blub! Blub.")[0].command, '.');
+    }
+
+    #[test]
+    fn test_dialect_extensions() {
+        let extensions = [Extension::Dump, Extension::Separator];
+        let brainfuck = Dialect::default().with_extensions(&extensions).unwrap();
+        let actual: String = brainfuck
+            .commands("+#\n,!.#\n!")
+            .iter()
+            .map(|c| c.command)
+            .collect();
+        assert_eq!(actual, "+#,!");
+        assert_eq!(
+            brainfuck.split_input("+#\n,!.#\n!"),
+            ("+#\n,!", Some(".#\n!"))
+        );
+        assert_eq!(brainfuck.split_input(",!"), (",!", Some("")));
+        assert_eq!(brainfuck.split_input(",#"), (",#", None));
+        assert_eq!(Dialect::default().split_input(",!a"), (",!a", None));
+
+        // `!` in a lexeme is not a separator
+        let ook = Dialect::new(DialectName::Ook)
+            .with_extensions(&extensions)
+            .unwrap();
+        assert_eq!(
+            ook.split_input("Ook. Ook!\nあ ! Ook."),
+            ("Ook. Ook!\nあ !", Some(" Ook."))
+        );
+        // nor in a word of a lexeme that does not form a whole lexeme
+        let program = "Ook. Ook. Ook! Ook.\nOok! Ook! Ook!\n";
+        assert_eq!(ook.split_input(program), (program, None));
+        let actual: String = ook.commands(program).iter().map(|c| c.command).collect();
+        assert_eq!(actual, "+.-");
+        let source = format!("{program}!ab");
+        assert_eq!(
+            ook.split_input(&source),
+            (&source[..program.len() + 1], Some("ab"))
+        );
+
+        let dialect = Dialect::parse(&CUSTOM.replace("\"aa\"", "\"#\"")).unwrap();
+        assert!(
+            dialect
+                .clone()
+                .with_extensions(&[Extension::Separator])
+                .is_ok()
+        );
+        let err = dialect.with_extensions(&[Extension::Dump]).unwrap_err();
+        assert_eq!(err, "`#` is already a lexeme of the dialect");
+    }
+
+    const CUSTOM: &str = "
+# longest lexeme wins
+increment = \"a\"
+decrement = \"aa\"
+right = \"r\"
+left = \"l\"
+loop_start = \"do\"
+loop_end = \"done\"
+output = \"put c\"
+input = \"get\"
+";
+
+    #[test]
+    fn test_dialect_parse() {
+        let content = CUSTOM;
+        let dialect = Dialect::parse(content).unwrap();
+        let actual: String = dialect
+            .commands("aaa done put  c get")
+            .iter()
+            .map(|c| c.command)
+            .collect();
+        assert_eq!(actual, "-+].,");
+
+        let err = |s: &str| Dialect::parse(s).unwrap_err();
+        assert!(err("increment = a").starts_with("1: "));
+        assert!(err("\nplus = \"a\"").starts_with("2: unknown field `plus`"));
+        assert!(err("increment = \"a\"").ends_with("missing field `decrement`"));
+        let duplicate = content.replace("\"aa\"", "\"a\"");
+        assert_eq!(err(&duplicate), "duplicate lexeme `a` for `decrement`");
+        let empty = content.replace("\"aa\"", "\" \"");
+        assert_eq!(err(&empty), "empty lexeme for `decrement`");
+    }
+}
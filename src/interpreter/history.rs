@@ -0,0 +1,104 @@
+use std::collections::VecDeque;
+
+const MAX_UNDO_ENTRIES: usize = 10_000;
+const CHECKPOINT_INTERVAL: usize = 4_096;
+const MAX_CHECKPOINTS: usize = 32;
+
+/// Records the information needed to undo executed steps.
+///
+/// The most recent steps are kept as fine-grained undo entries, and full checkpoints are taken
+/// periodically so that older steps can still be reached by restoring a checkpoint and replaying.
+/// Both are bounded, so the oldest history is discarded on long runs.
+pub struct History {
+    entries: VecDeque<Undo>,
+    checkpoints: VecDeque<Checkpoint>,
+    enabled: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            entries: VecDeque::new(),
+            checkpoints: VecDeque::new(),
+            enabled: true,
+        }
+    }
+}
+
+/// State changed by a single step.
+pub struct Undo {
+    pub cur: usize,
+    pub ptr: isize,
+    pub cell: Option<(isize, i64)>,
+    /// Whether a byte of the input was consumed.
+    pub input: bool,
+    pub output_len: usize,
+}
+
+/// Full interpreter state before the step `step_count`.
+#[derive(Clone)]
+pub struct Checkpoint {
+    pub step_count: usize,
+    pub memory: Vec<i64>,
+    pub origin: usize,
+    pub ptr: isize,
+    pub cur: usize,
+    pub input_pos: usize,
+    pub output: Vec<u8>,
+    pub counts: Vec<usize>,
+}
+
+impl History {
+    /// History that records nothing.
+    pub fn disabled() -> History {
+        History {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    pub fn push_undo(&mut self, undo: Undo) {
+        if !self.enabled {
+            return;
+        }
+        if self.entries.len() == MAX_UNDO_ENTRIES {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(undo);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<Undo> {
+        self.entries.pop_back()
+    }
+
+    pub fn clear_undo(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn needs_checkpoint(&self, step_count: usize) -> bool {
+        self.enabled
+            && self
+                .checkpoints
+                .back()
+                .is_none_or(|c| step_count >= c.step_count + CHECKPOINT_INTERVAL)
+    }
+
+    pub fn push_checkpoint(&mut self, checkpoint: Checkpoint) {
+        if self.checkpoints.len() == MAX_CHECKPOINTS {
+            self.checkpoints.pop_front();
+        }
+        self.checkpoints.push_back(checkpoint);
+    }
+
+    /// Returns the latest checkpoint taken before `step_count`, discarding the later ones.
+    pub fn checkpoint_before(&mut self, step_count: usize) -> Option<Checkpoint> {
+        while self
+            .checkpoints
+            .back()
+            .is_some_and(|c| c.step_count >= step_count)
+        {
+            self.checkpoints.pop_back();
+        }
+        self.checkpoints.back().cloned()
+    }
+}
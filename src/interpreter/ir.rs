@@ -0,0 +1,139 @@
+use super::{Token, TokenType};
+
+/// Optimized form of the program used by fast runs.
+///
+/// Each instruction covers a range of source tokens, so execution can stop at any instruction
+/// boundary and continue token by token with `Interpreter::step`.
+pub struct Program {
+    instrs: Vec<Instr>,
+    by_token: Vec<Option<usize>>, // index of the instruction starting at each token
+}
+
+pub struct Instr {
+    pub op: Op,
+    /// Index of the first token.
+    pub start: usize,
+    /// Number of tokens covered.
+    pub len: usize,
+}
+
+pub enum Op {
+    /// Adds to the current cell, folded from a run of `+` or `-`.
+    Add(i64),
+    /// Moves the pointer, folded from a run of `>` or `<`.
+    Move(isize),
+    /// `[-]` or `[+]`, which repeatedly adds the given delta until the current cell is 0.
+    Clear(i64),
+    /// Loop that decrements the current cell by one and adds a multiple of it to other cells at
+    /// the given offsets. `monotonic` is set if every cell only ever increases or decreases, and
+    /// `range` holds the lowest and highest offsets the pointer visits in the body.
+    MulLoop {
+        targets: Vec<(isize, i64)>,
+        monotonic: bool,
+        range: (isize, isize),
+    },
+    /// `[` with the index of the token following its matching `]`.
+    JumpIfZero(usize),
+    /// `]` with the index of the token following its matching `[`.
+    JumpIfNonZero(usize),
+    /// Any other single token, executed by `Interpreter::step`.
+    Step,
+}
+
+impl Program {
+    pub fn compile(tokens: &[Token], jump_targets: &[Option<usize>]) -> Program {
+        let mut instrs = Vec::new();
+        let mut by_token = vec![None; tokens.len()];
+        let mut i = 0;
+        while i < tokens.len() {
+            let (op, len) = compile_at(tokens, jump_targets, i);
+            by_token[i] = Some(instrs.len());
+            instrs.push(Instr { op, start: i, len });
+            i += len;
+        }
+        Program { instrs, by_token }
+    }
+
+    pub fn instrs(&self) -> &[Instr] {
+        &self.instrs
+    }
+
+    /// Returns the index of the instruction starting at the token, if any.
+    pub fn instr_at(&self, token: usize) -> Option<usize> {
+        self.by_token.get(token).copied().flatten()
+    }
+}
+
+fn compile_at(tokens: &[Token], jump_targets: &[Option<usize>], i: usize) -> (Op, usize) {
+    let tp = tokens[i].tp;
+    let run = tokens[i..].iter().take_while(|t| t.tp == tp).count();
+    match tp {
+        TokenType::Plus => (Op::Add(run as i64), run),
+        TokenType::Minus => (Op::Add(-(run as i64)), run),
+        TokenType::RightAngle => (Op::Move(run as isize), run),
+        TokenType::LeftAngle => (Op::Move(-(run as isize)), run),
+        TokenType::LeftSquare => match jump_targets[i] {
+            Some(end) => match compile_loop(&tokens[i + 1..end - 1]) {
+                Some(op) => (op, end - i),
+                None => (Op::JumpIfZero(end), 1),
+            },
+            None => (Op::Step, 1),
+        },
+        TokenType::RightSquare => match jump_targets[i] {
+            Some(start) => (Op::JumpIfNonZero(start), 1),
+            None => (Op::Step, 1),
+        },
+        TokenType::Dot | TokenType::Comma | TokenType::Hash => (Op::Step, 1),
+    }
+}
+
+/// Detects clear and multiply loops from the tokens between `[` and `]`.
+fn compile_loop(body: &[Token]) -> Option<Op> {
+    let mut offset = 0;
+    let mut range = (0, 0);
+    let mut deltas: Vec<(isize, i64)> = Vec::new();
+    let mut signs: Vec<(isize, i64)> = Vec::new();
+    let mut monotonic = true;
+    for t in body {
+        let delta = match t.tp {
+            TokenType::Plus => 1,
+            TokenType::Minus => -1,
+            TokenType::RightAngle => {
+                offset += 1;
+                range.1 = range.1.max(offset);
+                continue;
+            }
+            TokenType::LeftAngle => {
+                offset -= 1;
+                range.0 = range.0.min(offset);
+                continue;
+            }
+            _ => return None,
+        };
+        match deltas.iter_mut().find(|(o, _)| *o == offset) {
+            Some((_, d)) => *d += delta,
+            None => deltas.push((offset, delta)),
+        }
+        match signs.iter().find(|(o, _)| *o == offset) {
+            Some((_, s)) => monotonic &= *s == delta,
+            None => signs.push((offset, delta)),
+        }
+    }
+    if offset != 0 {
+        return None;
+    }
+    let origin = deltas.iter().find(|(o, _)| *o == 0).map_or(0, |(_, d)| *d);
+    let targets: Vec<_> = deltas
+        .into_iter()
+        .filter(|(o, d)| *o != 0 && *d != 0)
+        .collect();
+    match origin {
+        -1 | 1 if body.len() == 1 => Some(Op::Clear(origin)),
+        -1 => Some(Op::MulLoop {
+            targets,
+            monotonic,
+            range,
+        }),
+        _ => None,
+    }
+}
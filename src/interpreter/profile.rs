@@ -0,0 +1,186 @@
+use std::fmt::Write;
+
+/// Execution counts collected while the program runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub commands: Vec<CommandCount>,
+    pub loops: Vec<LoopCount>,
+    pub total_steps: usize,
+}
+
+/// Number of executions of a command, with the positions of the first and last characters of its
+/// lexeme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandCount {
+    pub command: char,
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub count: usize,
+}
+
+/// Executions of a loop, from the position of `[` to the position of its matching `]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopCount {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    /// Number of times the body was executed, which is the count of `]`.
+    pub iterations: usize,
+    /// Number of steps spent in the loop, including nested loops.
+    pub steps: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum ProfileFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl Profile {
+    /// Returns the largest execution count of a command.
+    pub fn max_count(&self) -> usize {
+        self.commands.iter().map(|c| c.count).max().unwrap_or(0)
+    }
+
+    /// Returns the ratio of the steps spent in the loop to all steps.
+    pub fn share(&self, l: &LoopCount) -> f64 {
+        if self.total_steps == 0 {
+            0.0
+        } else {
+            l.steps as f64 / self.total_steps as f64
+        }
+    }
+
+    pub fn export(&self, format: ProfileFormat) -> String {
+        match format {
+            ProfileFormat::Csv => self.to_csv(),
+            ProfileFormat::Json => self.to_json(),
+        }
+    }
+
+    /// Writes a row for each command and each loop, with 1-based lines and columns.
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,command,line,column,end_line,end_column,count,steps\n");
+        for c in &self.commands {
+            let ((line, pos), (end_line, end_pos)) = (c.start, c.end);
+            let _ = writeln!(
+                csv,
+                "command,{},{},{},{},{},{},{}",
+                c.command,
+                line + 1,
+                pos + 1,
+                end_line + 1,
+                end_pos + 1,
+                c.count,
+                c.count
+            );
+        }
+        for l in &self.loops {
+            let ((line, pos), (end_line, end_pos)) = (l.start, l.end);
+            let _ = writeln!(
+                csv,
+                "loop,[,{},{},{},{},{},{}",
+                line + 1,
+                pos + 1,
+                end_line + 1,
+                end_pos + 1,
+                l.iterations,
+                l.steps
+            );
+        }
+        csv
+    }
+
+    fn to_json(&self) -> String {
+        let position = |(line, pos): (usize, usize)| {
+            format!("{{\"line\": {}, \"column\": {}}}", line + 1, pos + 1)
+        };
+        let commands: Vec<String> = self
+            .commands
+            .iter()
+            .map(|c| {
+                format!(
+                    "{{\"command\": \"{}\", \"start\": {}, \"end\": {}, \"count\": {}}}",
+                    c.command,
+                    position(c.start),
+                    position(c.end),
+                    c.count
+                )
+            })
+            .collect();
+        let loops: Vec<String> = self
+            .loops
+            .iter()
+            .map(|l| {
+                format!(
+                    "{{\"start\": {}, \"end\": {}, \"iterations\": {}, \"steps\": {}, \"share\": {}}}",
+                    position(l.start),
+                    position(l.end),
+                    l.iterations,
+                    l.steps,
+                    self.share(l)
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"total_steps\": {},\n  \"commands\": [{}],\n  \"loops\": [{}]\n}}\n",
+            self.total_steps,
+            json_items(&commands),
+            json_items(&loops)
+        )
+    }
+}
+
+fn json_items(items: &[String]) -> String {
+    if items.is_empty() {
+        return String::new();
+    }
+    format!("\n    {}\n  ", items.join(",\n    "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{Interpreter, config::Config, dialect::Dialect};
+
+    #[test]
+    fn test_profile() {
+        let source = "++\n[>+++[-]<-]";
+        let mut interpreter = Interpreter::new(source, b"", Config::default(), &Dialect::default());
+        interpreter.run_fast(1000, &[], |_| false).unwrap();
+        let profile = interpreter.profile();
+
+        let counts: Vec<usize> = profile.commands.iter().map(|c| c.count).collect();
+        assert_eq!(counts, vec![1, 1, 1, 2, 2, 2, 2, 2, 6, 6, 2, 2, 2]);
+        assert_eq!(profile.total_steps, counts.iter().sum::<usize>());
+        assert_eq!(profile.max_count(), 6);
+
+        let outer = LoopCount {
+            start: (1, 0),
+            end: (1, 10),
+            iterations: 2,
+            steps: 29,
+        };
+        let inner = LoopCount {
+            start: (1, 5),
+            end: (1, 7),
+            iterations: 6,
+            steps: 14,
+        };
+        assert_eq!(profile.loops, vec![outer, inner]);
+        assert_eq!(profile.share(&outer), 29.0 / 31.0);
+
+        let csv = profile.export(ProfileFormat::Csv);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("kind,command,line,column,end_line,end_column,count,steps")
+        );
+        assert_eq!(lines.next(), Some("command,+,1,1,1,1,1,1"));
+        assert_eq!(lines.last(), Some("loop,[,2,6,2,8,6,14"));
+
+        let json = profile.export(ProfileFormat::Json);
+        assert!(json.starts_with("{\n  \"total_steps\": 31,\n  \"commands\": [\n    {\"command\": \"+\", \"start\": {\"line\": 1, \"column\": 1}, \"end\": {\"line\": 1, \"column\": 1}, \"count\": 1},\n"));
+        assert!(json.ends_with("{\"start\": {\"line\": 2, \"column\": 6}, \"end\": {\"line\": 2, \"column\": 8}, \"iterations\": 6, \"steps\": 14, \"share\": 0.45161290322580644}\n  ]\n}\n"));
+    }
+}
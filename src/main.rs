@@ -1,58 +1,426 @@
 mod app;
+mod config_file;
+mod convert;
+mod escape;
 mod event;
+mod format;
 mod interpreter;
+mod run;
 mod ui;
 mod widget;
 
 use std::{
-    fs::File,
-    io::Read,
+    fs::{self, File},
+    io::{self, Read},
+    process::ExitCode,
     sync::{Arc, RwLock},
 };
 
 use app::Speed;
-use clap::Parser;
+use clap::{CommandFactory, Parser, error::ErrorKind};
 use ratatui::{Terminal, backend::Backend};
 
-use crate::app::App;
+use crate::{
+    app::{App, AppInput},
+    interpreter::{
+        config::{CellSize, Config, EofPolicy, OverflowPolicy, TapeSize},
+        coverage::CoverageFormat,
+        dialect::{Dialect, DialectName, Extension},
+        profile::ProfileFormat,
+    },
+};
 
 /// termbf - Terminal Brainf*ck visualizer
 #[derive(Parser)]
-#[command(version)]
+#[command(version, arg_required_else_help = true)]
 struct Args {
-    /// brainf*ck source code file
-    #[arg(short = 's', long = "source", value_name = "FILE")]
-    source_file: String,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    /// show debug info
+    /// brainf*ck source code file, same as `termbf debug <FILE>`
+    #[arg(short = 's', long = "source", value_name = "FILE", hide = true)]
+    source_file: Option<String>,
+
+    /// same as `termbf check <FILE>` when used with `--source`
     #[arg(long, hide = true)]
+    check: bool,
+
+    #[command(flatten)]
+    interpreter: InterpreterArgs,
+
+    /// show debug info
+    #[arg(long, hide = true, global = true)]
     debug: bool,
 }
 
-fn run<B: Backend>(
+#[derive(clap::Subcommand)]
+enum Command {
+    /// Start the visualizer
+    Debug {
+        /// brainf*ck source code file
+        #[arg(value_name = "FILE")]
+        source_file: String,
+
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// wait for a line to be entered when `,` runs out of input, instead of reading EOF
+        #[arg(long)]
+        interactive: bool,
+    },
+
+    /// Run the program without the visualizer, reading stdin and writing stdout
+    Run {
+        /// brainf*ck source code file
+        #[arg(value_name = "FILE")]
+        source_file: String,
+
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// write the execution counts of the commands and the loops to the file
+        #[arg(long, value_name = "FILE")]
+        profile: Option<String>,
+
+        /// format of the profile [default: csv]
+        #[arg(long, value_name = "FORMAT", requires = "profile")]
+        profile_format: Option<ProfileFormat>,
+
+        /// write the coverage of the commands to the file, adding up the runs in an existing report
+        #[arg(long, value_name = "FILE")]
+        coverage: Option<String>,
+
+        /// format of the coverage report [default: lcov]
+        #[arg(long, value_name = "FORMAT", requires = "coverage")]
+        coverage_format: Option<CoverageFormat>,
+    },
+
+    /// Check the source code for unmatched brackets
+    Check {
+        /// brainf*ck source code file
+        #[arg(value_name = "FILE")]
+        source_file: String,
+    },
+
+    /// Format the source code by indenting loops, and print it to stdout
+    Fmt {
+        /// brainf*ck source code file
+        #[arg(value_name = "FILE")]
+        source_file: String,
+
+        /// overwrite the file instead of printing the result
+        #[arg(short, long)]
+        write: bool,
+
+        /// exit with an error if the file is not formatted, without printing the result
+        #[arg(long, conflicts_with = "write")]
+        check: bool,
+    },
+
+    /// Convert the source code to another dialect, and print it to stdout
+    Convert {
+        /// source code file
+        #[arg(value_name = "FILE")]
+        source_file: String,
+
+        /// dialect of the source code, same as `--dialect` [default: brainfuck]
+        #[arg(long, value_name = "NAME", conflicts_with_all = ["dialect", "dialect_file"])]
+        from: Option<DialectName>,
+
+        /// dialect to convert to
+        #[arg(long, value_name = "NAME", required_unless_present = "to_file")]
+        to: Option<DialectName>,
+
+        /// file defining the lexemes of a custom dialect to convert to
+        #[arg(long, value_name = "FILE", conflicts_with = "to")]
+        to_file: Option<String>,
+    },
+}
+
+#[derive(clap::Args)]
+struct InterpreterArgs {
+    /// size of each memory cell [default: u8]
+    #[arg(long, value_name = "SIZE", global = true)]
+    cell_size: Option<CellSize>,
+
+    /// behavior when a cell value goes out of range [default: error]
+    #[arg(long, value_name = "POLICY", global = true)]
+    cell_overflow: Option<OverflowPolicy>,
+
+    /// behavior when the pointer goes out of the tape [default: error]
+    #[arg(long, value_name = "POLICY", global = true)]
+    pointer_overflow: Option<OverflowPolicy>,
+
+    /// number of memory cells, or `infinite` to grow the tape on demand [default: 30000]
+    #[arg(long, value_name = "SIZE", global = true)]
+    tape_size: Option<TapeSize>,
+
+    /// value stored by `,` when the input is exhausted [default: zero]
+    #[arg(long, value_name = "POLICY", global = true)]
+    eof: Option<EofPolicy>,
+
+    /// config file [default: $XDG_CONFIG_HOME/termbf/config.toml]
+    #[arg(long = "config", value_name = "FILE", global = true)]
+    config_file: Option<String>,
+
+    /// language of the source code [default: brainfuck]
+    #[arg(long, value_name = "NAME", global = true)]
+    dialect: Option<DialectName>,
+
+    /// file defining the lexemes of a custom dialect
+    #[arg(long, value_name = "FILE", global = true, conflicts_with = "dialect")]
+    dialect_file: Option<String>,
+
+    /// enable a language extension, `#` to dump the memory or `!` to start the input, can be repeated
+    #[arg(long = "extension", value_name = "NAME", global = true)]
+    extensions: Vec<Extension>,
+}
+
+#[derive(clap::Args, Default)]
+struct InputArgs {
+    /// input given to the program
+    #[arg(long, value_name = "TEXT")]
+    input: Option<String>,
+
+    /// file whose contents are given to the program as input
+    #[arg(long, value_name = "FILE", conflicts_with = "input")]
+    input_file: Option<String>,
+}
+
+impl InputArgs {
+    fn read(&self) -> io::Result<Option<Vec<u8>>> {
+        match (&self.input, &self.input_file) {
+            (Some(input), _) => Ok(Some(input.as_bytes().to_vec())),
+            (None, Some(file)) => fs::read(file).map(Some),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+impl InterpreterArgs {
+    /// Command line options take precedence over the config file.
+    fn config(&self) -> io::Result<Config> {
+        let file = config_file::load(self.config_file.as_deref())?;
+        let default = Config::default();
+        Ok(Config {
+            cell_size: self
+                .cell_size
+                .or(file.cell_size)
+                .unwrap_or(default.cell_size),
+            cell_overflow: self
+                .cell_overflow
+                .or(file.cell_overflow)
+                .unwrap_or(default.cell_overflow),
+            pointer_overflow: self
+                .pointer_overflow
+                .or(file.pointer_overflow)
+                .unwrap_or(default.pointer_overflow),
+            tape_size: self
+                .tape_size
+                .or(file.tape_size)
+                .unwrap_or(default.tape_size),
+            eof: self.eof.or(file.eof).unwrap_or(default.eof),
+        })
+    }
+
+    fn dialect(&self) -> io::Result<Dialect> {
+        let dialect = match &self.dialect_file {
+            Some(path) => read_dialect_file(path)?,
+            None => Dialect::new(self.dialect.unwrap_or_default()),
+        };
+        self.with_extensions(dialect)
+    }
+
+    fn with_extensions(&self, dialect: Dialect) -> io::Result<Dialect> {
+        dialect
+            .with_extensions(&self.extensions)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    }
+}
+
+fn read_dialect_file(path: &str) -> io::Result<Dialect> {
+    let content = fs::read_to_string(path)?;
+    Dialect::parse(&content)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}:{e}")))
+}
+
+fn visualize<B: Backend>(
     terminal: &mut Terminal<B>,
+    source_file: String,
     source: String,
+    input: AppInput,
+    config: Config,
+    dialect: Dialect,
     debug: bool,
 ) -> Result<(), B::Error> {
     let speed = Arc::new(RwLock::new(Speed::Normal));
     let (_, rx) = event::new(speed.clone());
-    App::new(source, speed, debug).start(terminal, rx)
+    App::new(source_file, source, input, config, dialect, speed, debug).start(terminal, rx)
 }
 
-fn read_source_file(file: &str) -> std::io::Result<String> {
+fn check(source_file: &str, source: &str, dialect: &Dialect) -> ExitCode {
+    let diagnostics = interpreter::check(source, dialect);
+    for d in &diagnostics {
+        eprintln!("{source_file}:{d}");
+    }
+    if diagnostics.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+fn read_source_file(file: &str) -> io::Result<String> {
     let mut f = File::open(file)?;
     let mut source = String::new();
     f.read_to_string(&mut source)?;
     Ok(source)
 }
 
-fn main() -> std::io::Result<()> {
+fn fmt(
+    source_file: &str,
+    source: &str,
+    dialect: &Dialect,
+    write: bool,
+    check_only: bool,
+) -> io::Result<ExitCode> {
+    if !interpreter::check(source, dialect).is_empty() {
+        return Ok(check(source_file, source, dialect));
+    }
+    let formatted = format::format(source, dialect);
+    if check_only {
+        if formatted == source {
+            return Ok(ExitCode::SUCCESS);
+        }
+        eprintln!("{source_file}: not formatted");
+        return Ok(ExitCode::FAILURE);
+    }
+    if write {
+        if formatted != source {
+            fs::write(source_file, formatted)?;
+        }
+    } else {
+        print!("{formatted}");
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> io::Result<ExitCode> {
     let args = Args::parse();
-    let source = read_source_file(&args.source_file)?;
 
-    let mut terminal = ratatui::init();
-    let ret = run(&mut terminal, source, args.debug);
+    // `--source` (and `--check`) are kept from before the subcommands were added
+    let command = match (args.command, args.source_file) {
+        (Some(command), _) => command,
+        (None, Some(source_file)) if args.check => Command::Check { source_file },
+        (None, Some(source_file)) => Command::Debug {
+            source_file,
+            input: InputArgs::default(),
+            interactive: false,
+        },
+        (None, None) => Args::command()
+            .error(ErrorKind::MissingSubcommand, "a subcommand is required")
+            .exit(),
+    };
+
+    let dialect = args.interpreter.dialect()?;
+    match command {
+        Command::Debug {
+            source_file,
+            input,
+            interactive,
+        } => {
+            let source = read_source_file(&source_file)?;
+            let inline_input = dialect
+                .split_input(&source)
+                .1
+                .map(|s| s.as_bytes().to_vec());
+            let cli_input = input.read()?;
+            let input = AppInput {
+                from_source: cli_input.is_none() && dialect.has_extension(Extension::Separator),
+                bytes: cli_input.or(inline_input).unwrap_or_default(),
+                file: input.input_file,
+                interactive,
+            };
+            let config = args.interpreter.config()?;
+
+            let mut terminal = ratatui::init();
+            let ret = visualize(
+                &mut terminal,
+                source_file,
+                source,
+                input,
+                config,
+                dialect,
+                args.debug,
+            );
 
-    ratatui::restore();
-    ret
+            ratatui::restore();
+            ret.map(|_| ExitCode::SUCCESS)
+        }
+        Command::Run {
+            source_file,
+            input,
+            profile,
+            profile_format,
+            coverage,
+            coverage_format,
+        } => {
+            let source = read_source_file(&source_file)?;
+            if !interpreter::check(&source, &dialect).is_empty() {
+                return Ok(check(&source_file, &source, &dialect));
+            }
+            let inline_input = dialect
+                .split_input(&source)
+                .1
+                .map(|s| s.as_bytes().to_vec());
+            let input = input.read()?.or(inline_input);
+            let config = args.interpreter.config()?;
+            let profile = profile
+                .as_deref()
+                .map(|file| (file, profile_format.unwrap_or_default()));
+            let coverage = coverage
+                .as_deref()
+                .map(|file| (file, coverage_format.unwrap_or_default()));
+            run::run(
+                &source_file,
+                &source,
+                input,
+                config,
+                &dialect,
+                profile,
+                coverage,
+            )
+        }
+        Command::Check { source_file } => {
+            let source = read_source_file(&source_file)?;
+            Ok(check(&source_file, &source, &dialect))
+        }
+        Command::Fmt {
+            source_file,
+            write,
+            check,
+        } => {
+            let source = read_source_file(&source_file)?;
+            fmt(&source_file, &source, &dialect, write, check)
+        }
+        Command::Convert {
+            source_file,
+            from,
+            to,
+            to_file,
+        } => {
+            let source = read_source_file(&source_file)?;
+            let from = match from {
+                Some(from) => args.interpreter.with_extensions(Dialect::new(from))?,
+                None => dialect,
+            };
+            let to = match (to, to_file) {
+                (_, Some(path)) => read_dialect_file(&path)?,
+                (to, None) => Dialect::new(to.unwrap_or_default()),
+            };
+            let to = args.interpreter.with_extensions(to)?;
+            print!("{}", convert::convert(&source, &from, &to));
+            Ok(ExitCode::SUCCESS)
+        }
+    }
 }
@@ -0,0 +1,168 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use crate::interpreter::{
+    Interpreter, RunOutcome,
+    config::Config,
+    coverage::{Coverage, CoverageFormat},
+    dialect::Dialect,
+    profile::ProfileFormat,
+};
+
+/// Number of instructions executed between writes of the output.
+const RUN_CHUNK: usize = 100_000;
+/// Maximum number of bytes read from stdin at once.
+const STDIN_CHUNK: usize = 4096;
+
+/// Runs the program without the visualizer, reading the input from stdin if not given.
+///
+/// stdin is read only when `,` needs more input, so interactive programs see their output
+/// before the input is requested. The profile and the coverage are written to the files if given,
+/// also when the program fails.
+pub fn run(
+    source_file: &str,
+    source: &str,
+    input: Option<Vec<u8>>,
+    config: Config,
+    dialect: &Dialect,
+    profile: Option<(&str, ProfileFormat)>,
+    coverage: Option<(&str, CoverageFormat)>,
+) -> io::Result<ExitCode> {
+    let interpreter = match input {
+        Some(input) => Interpreter::new(source, &input, config, dialect),
+        None => Interpreter::new(source, &[], config, dialect).with_interactive_input(),
+    };
+    let mut interpreter = interpreter.without_history();
+    let exit_code = run_interpreter(
+        source_file,
+        &mut interpreter,
+        io::stdin().lock(),
+        io::stdout().lock(),
+        io::stderr().lock(),
+    )?;
+    let result = interpreter.profile();
+    if let Some((file, format)) = profile {
+        fs::write(file, result.export(format))?;
+    }
+    if let Some((file, format)) = coverage {
+        let report = export_coverage(&Coverage::from_profile(&result), source_file, file, format)?;
+        fs::write(file, report)?;
+    }
+    Ok(exit_code)
+}
+
+/// Exports the coverage merged with the existing report in the file, so that the runs with
+/// different inputs add up.
+fn export_coverage(
+    coverage: &Coverage,
+    source_file: &str,
+    file: &str,
+    format: CoverageFormat,
+) -> io::Result<String> {
+    match fs::read_to_string(file) {
+        Ok(existing) => coverage
+            .export_merged(source_file, format, &existing)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{file}: {e}"))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(coverage.export(source_file, format)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Runs the program until it ends, reading `,` input from `stdin` and reporting dumps and errors to
+/// `stderr`.
+fn run_interpreter(
+    source_file: &str,
+    interpreter: &mut Interpreter,
+    mut stdin: impl Read,
+    mut stdout: impl Write,
+    mut stderr: impl Write,
+) -> io::Result<ExitCode> {
+    let mut written = 0;
+    loop {
+        let result = interpreter.run_fast(RUN_CHUNK, &[], |_| false);
+
+        let output = &interpreter.output()[written..];
+        match stdout.write_all(output).and_then(|_| stdout.flush()) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(ExitCode::SUCCESS),
+            ret => ret?,
+        }
+        written += output.len();
+
+        match result {
+            Ok(RunOutcome::Running) => {}
+            Ok(RunOutcome::Dumped) => writeln!(stderr, "{source_file}:{}", interpreter.dump())?,
+            Ok(RunOutcome::NeedsInput) => {
+                let mut buf = [0; STDIN_CHUNK];
+                match stdin.read(&mut buf)? {
+                    0 => interpreter.close_input(),
+                    n => interpreter.push_input(&buf[..n]),
+                }
+            }
+            Ok(RunOutcome::Paused | RunOutcome::Halted) => return Ok(ExitCode::SUCCESS),
+            Err(e) => {
+                let (line, pos) = e.line_and_pos();
+                writeln!(
+                    stderr,
+                    "{source_file}:{}:{}: {} (step {})",
+                    line + 1,
+                    pos + 1,
+                    e.message(),
+                    e.step()
+                )?;
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::config::{OverflowPolicy, TapeSize};
+
+    fn run_headless(source: &str, input: &[u8], config: Config) -> (ExitCode, String, String) {
+        let mut interpreter = Interpreter::new(source, &[], config, &Dialect::default())
+            .with_interactive_input()
+            .without_history();
+        let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+        let exit_code =
+            run_interpreter("a.bf", &mut interpreter, input, &mut stdout, &mut stderr).unwrap();
+        let stdout = String::from_utf8(stdout).unwrap();
+        (exit_code, stdout, String::from_utf8(stderr).unwrap())
+    }
+
+    #[test]
+    fn test_run_interpreter() {
+        let (exit_code, stdout, stderr) = run_headless(",[.,]", b"abc", Config::default());
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "abc");
+        assert_eq!(stderr, "");
+
+        // the output written before the error is kept
+        let (exit_code, stdout, stderr) = run_headless("+++.\n-----", b"", Config::default());
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(stdout, "\x03");
+        assert_eq!(stderr, "a.bf:2:4: cell underflow (step 8)\n");
+
+        // the loop body steps past the left edge of the tape before coming back
+        let source = ">+[-<<>>>+<]";
+        let (exit_code, stdout, stderr) = run_headless(source, b"", Config::default());
+        assert_eq!(exit_code, ExitCode::FAILURE);
+        assert_eq!(stdout, "");
+        assert_eq!(stderr, "a.bf:1:6: pointer out of range (step 6)\n");
+
+        // saturating at the edge moves the cell changed by the loop, printed from the last cell
+        let config = Config {
+            pointer_overflow: OverflowPolicy::Saturate,
+            tape_size: TapeSize::Fixed(4),
+            ..Default::default()
+        };
+        let (exit_code, stdout, stderr) = run_headless(&format!("{source}<<<<>>>."), b"", config);
+        assert_eq!(exit_code, ExitCode::SUCCESS);
+        assert_eq!(stdout, "\x01");
+        assert_eq!(stderr, "");
+    }
+}
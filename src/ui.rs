@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use ratatui::{
     Frame,
     layout::{Constraint, Layout, Rect},
@@ -5,15 +7,44 @@ use ratatui::{
     text::{Line, Span, Text},
     widgets::{Block, BorderType, Borders, Padding, Paragraph, Wrap},
 };
+use tui_input::Input;
 
 use crate::{
-    app::{App, EditState, SelectItem, Speed, State},
-    widget::memory::Memory,
+    app::{App, EditState, MemoryLayout, SelectItem, SourceView, Speed, State},
+    escape,
+    interpreter::{
+        RuntimeError,
+        config::{CellSize, EofPolicy, OverflowPolicy},
+        coverage::Coverage,
+        dialect::Extension,
+        profile::Profile,
+    },
+    widget::memory::{CellFormat, Memory},
 };
 
 const APP_COLOR: Color = Color::Yellow;
 const DEFAULT_COLOR: Color = Color::Reset;
 const DISABLED_COLOR: Color = Color::DarkGray;
+const ERROR_COLOR: Color = Color::Red;
+const BREAKPOINT_COLOR: Color = Color::Red;
+const CONDITIONAL_BREAKPOINT_COLOR: Color = Color::Magenta;
+const WATCH_COLOR: Color = Color::Red;
+
+/// Colors of the heatmap from the least to the most executed commands.
+const HEATMAP_COLORS: [Color; 5] = [
+    Color::Blue,
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Red,
+];
+const HOT_LOOPS_WIDTH: u16 = 36;
+
+/// Number of the latest dumps shown in the Log panel.
+const LOG_LINES: u16 = 3;
+
+/// Maximum number of lines shown in the Diagnostics panel.
+const DIAGNOSTIC_LINES: u16 = 3;
 
 pub fn render(f: &mut Frame, app: &App) {
     use Constraint::*;
@@ -22,6 +53,7 @@ pub fn render(f: &mut Frame, app: &App) {
         Length(1),
         Min(0),
         Length(3),
+        Length(1),
         Length(2),
         Length(debug_area_length),
     ];
@@ -30,8 +62,9 @@ pub fn render(f: &mut Frame, app: &App) {
     render_header(f, chunks[0]);
     render_outputs(f, chunks[1], app);
     render_controls(f, chunks[2], app);
-    render_help(f, chunks[3], app);
-    render_debug_info(f, chunks[4], app);
+    render_status(f, chunks[3], app);
+    render_help(f, chunks[4], app);
+    render_debug_info(f, chunks[5], app);
 }
 
 fn render_header(f: &mut Frame, area: Rect) {
@@ -41,75 +74,157 @@ fn render_header(f: &mut Frame, area: Rect) {
 
 fn render_outputs(f: &mut Frame, area: Rect, app: &App) {
     use Constraint::*;
-    let constraints = vec![Min(3), Length(3), Length(3), Length(3)];
+    let log_area_length = if app.dialect.has_extension(Extension::Dump) {
+        2 + LOG_LINES
+    } else {
+        0
+    };
+    let error_area_length = if app.runtime_error.is_some() { 3 } else { 0 };
+    let diagnostic_count = app.interpreter.diagnostics().len() as u16;
+    let diagnostic_area_length = if diagnostic_count > 0 {
+        2 + diagnostic_count.min(DIAGNOSTIC_LINES)
+    } else {
+        0
+    };
+    let row_height = 1 + u16::from(app.ascii_row);
+    let memory_area_length = match app.memory_layout.columns() {
+        None => 3 + row_height,
+        Some(_) => {
+            // leave the minimum height to the other panels
+            let fixed = 3 * 3 + log_area_length + error_area_length + diagnostic_area_length;
+            let max_rows = area
+                .height
+                .saturating_sub(fixed + 3 /* border and header */);
+            let rows = (app.memory_rows as u16).min(max_rows / row_height).max(1);
+            3 + rows * row_height
+        }
+    };
+    let constraints = vec![
+        Min(3),
+        Length(3),
+        Length(memory_area_length),
+        Length(3),
+        Length(log_area_length),
+        Length(error_area_length),
+        Length(diagnostic_area_length),
+    ];
     let chunks = Layout::vertical(constraints).split(area);
 
-    let source = source_text(app);
-    let source_area = build_textarea(app, "Source", source, SelectItem::Source);
-    f.render_widget(source_area, chunks[0]);
+    let profile = (app.source_view == SourceView::Heatmap).then(|| app.interpreter.profile());
+    let coverage = (app.source_view == SourceView::Coverage).then(|| app.coverage());
+    let source_chunks = if profile.is_some() {
+        Layout::horizontal([Min(0), Length(HOT_LOOPS_WIDTH)]).split(chunks[0])
+    } else {
+        Layout::horizontal([Min(0)]).split(chunks[0])
+    };
+    let source = source_text(app, profile.as_ref(), coverage.as_ref());
+    let label = source_label(app, coverage.as_ref());
+    let source_area = build_textarea(app, &label, source, SelectItem::Source);
+    f.render_widget(source_area, source_chunks[0]);
+    app.source_view_height
+        .set(source_chunks[0].height.saturating_sub(2 /* border */) as usize);
+    if let Some(profile) = &profile {
+        f.render_widget(build_hot_loops(app, profile), source_chunks[1]);
+    }
 
     let input = input_content(app, chunks[1]);
-    let input_area = build_textarea(app, "Input", input, SelectItem::Input);
+    let label = input_label(app);
+    let input_area = build_textarea(app, &label, input, SelectItem::Input);
     f.render_widget(input_area, chunks[1]);
     set_input_cursor(f, app, chunks[1]);
 
     let mem = app.interpreter.memory();
-    let memory = build_memory(app, "Memory", mem, SelectItem::Memory);
+    let label = memory_label(app);
+    let memory = build_memory(app, &label, mem, SelectItem::Memory, chunks[2]);
     f.render_widget(memory, chunks[2]);
 
     let output = output_content(app, chunks[3]);
-    let output_area = build_textarea(app, "Output", output, SelectItem::Output);
+    let label = if app.output_raw {
+        "Output (raw)"
+    } else {
+        "Output"
+    };
+    let output_area = build_textarea(app, label, output, SelectItem::Output);
     f.render_widget(output_area, chunks[3]);
+
+    if log_area_length > 0 {
+        f.render_widget(build_log_area(app), chunks[4]);
+    }
+
+    if let Some(err) = &app.runtime_error {
+        let error_area = build_error_area(err);
+        f.render_widget(error_area, chunks[5]);
+    }
+
+    if diagnostic_area_length > 0 {
+        f.render_widget(build_diagnostic_area(app), chunks[6]);
+    }
 }
 
 fn render_controls(f: &mut Frame, area: Rect, app: &App) {
     use Constraint::*;
-    let (reset_area, start_area, pause_area, step_area, speed_area) = match app.state {
-        State::Default => {
-            let constraints = vec![Min(0), Length(9), Length(8), Length(18), Min(0)];
-            let cs = Layout::horizontal(constraints).split(area);
-            (None, Some(cs[1]), None, Some(cs[2]), Some(cs[3]))
-        }
-        State::Stop => {
-            let constraints = vec![Min(0), Length(9), Min(0)];
-            let cs = Layout::horizontal(constraints).split(area);
-            (Some(cs[1]), None, None, None, None)
-        }
-        State::Play => {
-            let constraints = vec![Min(0), Length(9), Length(9), Length(8), Length(18), Min(0)];
-            let cs = Layout::horizontal(constraints).split(area);
-            (Some(cs[1]), Some(cs[2]), None, Some(cs[3]), Some(cs[4]))
-        }
-        State::AutoPlay => {
-            let constraints = vec![Min(0), Length(9), Length(9), Length(8), Length(18), Min(0)];
-            let cs = Layout::horizontal(constraints).split(area);
-            (Some(cs[1]), None, Some(cs[2]), Some(cs[3]), Some(cs[4]))
-        }
+    use SelectItem::*;
+    let items: &[SelectItem] = match app.state {
+        State::Default => &[Start, Run, Step, Speed],
+        State::Stop | State::Failed => &[Reset, Back],
+        State::Invalid => &[Reset, Start, Run, Step],
+        State::Play => &[Reset, Start, Run, Back, Step, Speed],
+        State::AutoPlay => &[Reset, Pause, Run, Back, Step, Speed],
+        State::FastRun => &[Reset, Pause],
     };
 
-    if let Some(area) = reset_area {
-        let reset_button = build_button(app, "Reset", SelectItem::Reset);
-        f.render_widget(reset_button, area);
-    }
-
-    if let Some(area) = start_area {
-        let start_button = build_button(app, "Start", SelectItem::Start);
-        f.render_widget(start_button, area);
+    let constraints = std::iter::once(Min(0))
+        .chain(items.iter().map(|item| Length(control_width(*item))))
+        .chain(std::iter::once(Min(0)));
+    let cs = Layout::horizontal(constraints).split(area);
+
+    for (item, area) in items.iter().zip(cs.iter().skip(1)) {
+        match item {
+            Reset => f.render_widget(build_button(app, "Reset", Reset), *area),
+            Start => f.render_widget(build_button(app, "Start", Start), *area),
+            Pause => f.render_widget(build_button(app, "Pause", Pause), *area),
+            Run => f.render_widget(build_button(app, "Run", Run), *area),
+            Back => f.render_widget(build_button(app, "Back", Back), *area),
+            Step => f.render_widget(build_button(app, "Step", Step), *area),
+            Speed => f.render_widget(build_speed_select(app, Speed), *area),
+            _ => {}
+        }
     }
+}
 
-    if let Some(area) = pause_area {
-        let pause_button = build_button(app, "Pause", SelectItem::Pause);
-        f.render_widget(pause_button, area);
+fn control_width(item: SelectItem) -> u16 {
+    match item {
+        SelectItem::Speed => 18,
+        SelectItem::Back | SelectItem::Step => 8,
+        SelectItem::Run => 7,
+        _ => 9,
     }
+}
 
-    if let Some(area) = step_area {
-        let step_button = build_button(app, "Step", SelectItem::Step);
-        f.render_widget(step_button, area);
+fn render_status(f: &mut Frame, area: Rect, app: &App) {
+    if app.edit_state.prompt() {
+        let prompt = prompt_label(app);
+        let cursor_x = area.x + 1 + (prompt.len() + app.prompt_input.visual_cursor()) as u16;
+        let prompt = Paragraph::new(format!("{prompt}{}", app.prompt_input.value()))
+            .style(Style::default().fg(APP_COLOR))
+            .block(Block::default().padding(Padding::horizontal(1)));
+        f.render_widget(prompt, area);
+        f.set_cursor_position((cursor_x.min(area.right().saturating_sub(1)), area.y));
+        return;
     }
+    let status = build_status(app);
+    f.render_widget(status, area);
+}
 
-    if let Some(area) = speed_area {
-        let speed_select = build_speed_select(app, SelectItem::Speed);
-        f.render_widget(speed_select, area);
+fn prompt_label(app: &App) -> String {
+    match app.edit_state {
+        EditState::EditWatchValue => format!("Pause when cell {} becomes: ", app.memory_cursor),
+        EditState::EditBreakpointCondition => {
+            let (line, pos) = app.source_cursor;
+            format!("Break at line {}, column {} if: ", line + 1, pos + 1)
+        }
+        EditState::EditBreakCondition => "Break when: ".to_string(),
+        EditState::None | EditState::EditInput | EditState::EnterInputLine => String::new(),
     }
 }
 
@@ -125,70 +240,191 @@ fn render_debug_info(f: &mut Frame, area: Rect, app: &App) {
     }
 }
 
-fn source_text(app: &App) -> Text<'_> {
+fn source_text<'a>(
+    app: &'a App,
+    profile: Option<&Profile>,
+    coverage: Option<&Coverage>,
+) -> Text<'a> {
     let base_style = if app.selected == SelectItem::Source {
         Style::default().fg(DEFAULT_COLOR)
     } else {
         Style::default().fg(DISABLED_COLOR)
     };
-    let cur_lp = app.interpreter.current_line_and_pos();
-
-    if app.state == State::Default || cur_lp.is_none() {
-        let lines: Vec<Line> = app
-            .source
-            .iter()
-            .skip(app.source_scroll_offset)
-            .map(|line| Line::styled(line, base_style))
-            .collect();
-        return Text::from(lines);
+    let error_style = Style::default()
+        .fg(ERROR_COLOR)
+        .add_modifier(Modifier::BOLD | Modifier::REVERSED);
+
+    let mut highlights: HashMap<(usize, usize), Style> = HashMap::new();
+    if let Some(profile) = profile {
+        let max = profile.max_count();
+        for c in &profile.commands {
+            let style = base_style.fg(heat_color(c.count, max));
+            for p in lexeme_positions(app, c.start) {
+                highlights.insert(p, style);
+            }
+        }
+    }
+    if let Some(coverage) = coverage {
+        let uncovered = coverage.commands().iter().filter(|c| c.count == 0);
+        for c in uncovered {
+            for p in lexeme_positions(app, c.start) {
+                highlights.insert(p, base_style.fg(ERROR_COLOR));
+            }
+        }
+    }
+    for (bp, condition) in &app.breakpoints {
+        let color = match condition {
+            Some(_) => CONDITIONAL_BREAKPOINT_COLOR,
+            None => BREAKPOINT_COLOR,
+        };
+        for p in lexeme_positions(app, *bp) {
+            let style = highlights.get(&p).copied().unwrap_or(base_style);
+            highlights.insert(p, style.bg(color));
+        }
+    }
+    for d in app.interpreter.diagnostics() {
+        for p in lexeme_positions(app, d.line_and_pos()) {
+            highlights.insert(p, error_style);
+        }
+    }
+    if !matches!(app.state, State::Default | State::Invalid) {
+        if let Some(lp) = app.interpreter.current_line_and_pos() {
+            let cur_color = if app.state == State::Failed {
+                ERROR_COLOR
+            } else {
+                APP_COLOR
+            };
+            let cur_style = Style::default().fg(cur_color).add_modifier(Modifier::BOLD);
+            for p in lexeme_positions(app, lp) {
+                let style = match highlights.get(&p) {
+                    Some(style) => style.patch(cur_style),
+                    None => cur_style,
+                };
+                highlights.insert(p, style);
+            }
+        }
+    }
+    if app.selected == SelectItem::Source {
+        for p in lexeme_positions(app, app.source_cursor) {
+            let cursor_style = match highlights.get(&p) {
+                Some(style) => style.add_modifier(Modifier::REVERSED),
+                None => base_style.add_modifier(Modifier::REVERSED),
+            };
+            highlights.insert(p, cursor_style);
+        }
     }
 
-    let cur_style = Style::default().fg(APP_COLOR).add_modifier(Modifier::BOLD);
-
-    let (cur_line, cur_pos) = cur_lp.unwrap();
     let lines: Vec<Line> = app
         .source
         .iter()
         .enumerate()
         .skip(app.source_scroll_offset)
-        .map(|(i, line)| {
-            if i == cur_line {
-                let mut cs = line.chars();
-                let init = cs.by_ref().take(cur_pos).collect::<String>();
-                let cur = cs.next().unwrap();
-                let tail = cs.collect::<String>();
-                Line::from(vec![
-                    Span::styled(init, base_style),
-                    Span::styled(cur.to_string(), cur_style),
-                    Span::styled(tail, base_style),
-                ])
-            } else {
-                Line::styled(line, base_style)
-            }
-        })
+        .map(|(i, line)| source_line(i, line, base_style, &highlights))
         .collect();
     Text::from(lines)
 }
 
-fn input_content(app: &App, area: Rect) -> &str {
-    let input = if app.interpreter.running() {
-        app.interpreter.input()
-    } else {
-        app.input_input.value()
+/// Returns the heatmap color of a command on a log scale, so that commands executed a few times
+/// are still distinguishable from the hot ones.
+fn heat_color(count: usize, max: usize) -> Color {
+    if count == 0 {
+        return DISABLED_COLOR;
+    }
+    let level = (count as f64).ln_1p() / (max as f64).ln_1p();
+    let idx = (level * HEATMAP_COLORS.len() as f64) as usize;
+    HEATMAP_COLORS[idx.min(HEATMAP_COLORS.len() - 1)]
+}
+
+/// Returns the positions of the characters of the command starting at the position, which may
+/// span multiple characters and lines in dialects, or only the position if it is not a command.
+fn lexeme_positions(app: &App, start: (usize, usize)) -> Vec<(usize, usize)> {
+    let Some(end) = app.interpreter.command_end(start) else {
+        return vec![start];
     };
+    (start.0..=end.0)
+        .flat_map(|line| {
+            let from = if line == start.0 { start.1 } else { 0 };
+            let to = if line == end.0 {
+                end.1 + 1
+            } else {
+                app.source.get(line).map_or(0, |s| s.chars().count())
+            };
+            (from..to).map(move |pos| (line, pos))
+        })
+        .collect()
+}
+
+fn source_line<'a>(
+    i: usize,
+    line: &'a str,
+    base_style: Style,
+    highlights: &HashMap<(usize, usize), Style>,
+) -> Line<'a> {
+    let mut spans: Vec<Span> = Vec::new();
+    let mut current: Option<(String, Style)> = None;
+    for (pos, c) in line.chars().enumerate() {
+        let style = highlights.get(&(i, pos)).copied().unwrap_or(base_style);
+        match current.as_mut() {
+            Some((s, st)) if *st == style => s.push(c),
+            _ => {
+                if let Some((s, st)) = current.take() {
+                    spans.push(Span::styled(s, st));
+                }
+                current = Some((c.to_string(), style));
+            }
+        }
+    }
+    if let Some((s, st)) = current {
+        spans.push(Span::styled(s, st));
+    }
+    Line::from(spans).style(base_style)
+}
+
+/// Returns the text field edited in the Input panel, if any.
+fn editing_input(app: &App) -> Option<&Input> {
+    match app.edit_state {
+        EditState::EditInput => Some(&app.input_input),
+        EditState::EnterInputLine => Some(&app.line_input),
+        _ => None,
+    }
+}
 
-    if app.edit_state == EditState::EditInput {
+fn input_content(app: &App, area: Rect) -> String {
+    if let Some(input) = editing_input(app) {
         let max_width = area.width - 4 /* border + padding */;
-        let input_start = input.len().saturating_sub(max_width as usize);
-        &input[input_start..]
+        last_chars(input.value(), max_width as usize).to_string()
     } else {
-        input
+        let input = if app.interpreter.running() {
+            app.interpreter.input()
+        } else {
+            &app.input_bytes
+        };
+        // only the beginning fits in the panel
+        let max_len = (area.width as usize) * 4;
+        escape::preview(&input[..input.len().min(max_len)])
+    }
+}
+
+fn input_label(app: &App) -> String {
+    let Some(input) = editing_input(app) else {
+        return match &app.input_file {
+            Some(file) => format!("Input - {file}"),
+            None => "Input".to_string(),
+        };
+    };
+    match escape::unescape(input.value()) {
+        Ok(mut bytes) if app.edit_state == EditState::EnterInputLine => {
+            bytes.push(b'\n');
+            format!("Input - waiting for a line: {}", escape::preview(&bytes))
+        }
+        Ok(bytes) => format!("Input - {} bytes: {}", bytes.len(), escape::preview(&bytes)),
+        Err(e) => format!("Input - {e}"),
     }
 }
 
 fn set_input_cursor(f: &mut Frame, app: &App, area: Rect) {
-    if app.edit_state == EditState::EditInput {
-        let visual_cursor = app.input_input.visual_cursor() as u16;
+    if let Some(input) = editing_input(app) {
+        let visual_cursor = input.visual_cursor() as u16;
         let max_width = area.width - 4 /* border + padding */;
         let cursor_x = area.x + 2 /* border + padding */ + visual_cursor.min(max_width);
         let cursor_y = area.y + 1 /* border */;
@@ -196,12 +432,53 @@ fn set_input_cursor(f: &mut Frame, app: &App, area: Rect) {
     }
 }
 
-fn output_content(app: &App, area: Rect) -> &str {
+fn output_content(app: &App, area: Rect) -> String {
     let output = app.interpreter.output();
+    let max_width = (area.width - 4/* border + padding */) as usize;
 
-    let max_width = area.width - 4 /* border + padding */;
-    let output_start = output.len().saturating_sub(max_width as usize);
-    &output[output_start..]
+    if app.output_raw {
+        let n = (max_width + 1) / 3;
+        let bytes = &output[output.len().saturating_sub(n)..];
+        return bytes
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect::<Vec<_>>()
+            .join(" ");
+    }
+
+    // decode only the end of the output, starting at a character boundary
+    let mut start = output.len().saturating_sub(max_width * 4);
+    while start > 0 && output[start] & 0xC0 == 0x80 {
+        start -= 1;
+    }
+    let decoded = decode_utf8(&output[start..]);
+    last_chars(&decoded, max_width).to_string()
+}
+
+/// Decodes the bytes as UTF-8, replacing invalid sequences with U+FFFD.
+///
+/// An incomplete character at the end is omitted, since the rest of its bytes may follow.
+fn decode_utf8(bytes: &[u8]) -> String {
+    let mut decoded = String::with_capacity(bytes.len());
+    let mut chunks = bytes.utf8_chunks().peekable();
+    while let Some(chunk) = chunks.next() {
+        decoded.push_str(chunk.valid());
+        let invalid = chunk.invalid();
+        let incomplete = chunks.peek().is_none()
+            && std::str::from_utf8(invalid).is_err_and(|e| e.error_len().is_none());
+        if !invalid.is_empty() && !incomplete {
+            decoded.push(char::REPLACEMENT_CHARACTER);
+        }
+    }
+    decoded
+}
+
+fn last_chars(s: &str, n: usize) -> &str {
+    if n == 0 {
+        return "";
+    }
+    let start = s.char_indices().rev().nth(n - 1).map_or(0, |(i, _)| i);
+    &s[start..]
 }
 
 fn build_header(label: &str) -> Paragraph<'_> {
@@ -235,20 +512,64 @@ where
 fn build_memory<'a>(
     app: &'a App,
     label: &'a str,
-    mem: &'a Vec<u8>,
+    mem: &'a [i64],
     item: SelectItem,
+    area: Rect,
 ) -> Memory<'a> {
     let cur_ptr = match app.state {
-        State::Default | State::Stop => None,
-        State::Play | State::AutoPlay => Some(app.interpreter.current_ptr()),
+        State::Default | State::Stop | State::Invalid => None,
+        State::Play | State::AutoPlay | State::FastRun | State::Failed => {
+            let i = &app.interpreter;
+            Some((i.current_ptr() - i.memory_start()) as usize)
+        }
+    };
+    let i = &app.interpreter;
+    let index = |addr: isize| usize::try_from(addr - i.memory_start()).ok();
+    let cursor = if app.selected == item {
+        index(app.memory_cursor)
+    } else {
+        None
+    };
+    let marks = app.watches.keys().filter_map(|addr| index(*addr));
+    let cell_size = i.config().cell_size;
+    // follow the cursor while the panel is selected, and the pointer otherwise
+    let follow = if app.selected == item {
+        Some(app.memory_cursor)
+    } else {
+        Some(i.current_ptr())
     };
-    Memory::new(mem, cur_ptr)
+    let memory = Memory::new(mem, cell_size, cur_ptr)
+        .format(app.cell_format)
+        .ascii_row(app.ascii_row)
+        .ruler(i.memory_start(), Style::default().fg(DISABLED_COLOR));
+    let width = area.width.saturating_sub(4 /* border and padding */) as usize;
+    let columns = app
+        .memory_layout
+        .columns()
+        .map(|columns| memory.grid_columns(width, columns));
+    match columns {
+        Some(columns) => {
+            app.memory_view_width.set(columns);
+            let rows = area.height.saturating_sub(3 /* border and header */) as usize;
+            app.memory_view_height.set(rows / memory.row_height());
+        }
+        None => {
+            app.memory_view_width.set(memory.visible_cells(width));
+            app.memory_view_height.set(1);
+        }
+    }
+    let offset = app.scroll_memory_to(follow);
+    memory
+        .grid(columns)
+        .offset(offset)
         .style(get_style_base(app, item, DEFAULT_COLOR, DISABLED_COLOR))
         .ptr_style(
             Style::default()
                 .fg(APP_COLOR)
                 .add_modifier(Modifier::UNDERLINED),
         )
+        .cursor(cursor, Style::default().add_modifier(Modifier::REVERSED))
+        .marks(marks, Style::default().bg(WATCH_COLOR))
         .block(
             Block::bordered()
                 .title(label)
@@ -257,6 +578,150 @@ fn build_memory<'a>(
         )
 }
 
+fn source_label(app: &App, coverage: Option<&Coverage>) -> String {
+    let mut label = "Source".to_string();
+    if let Some(coverage) = coverage {
+        let runs = match coverage.runs() {
+            1 => "1 run".to_string(),
+            n => format!("{n} runs"),
+        };
+        label.push_str(&format!(
+            " - coverage {}/{} commands in {runs}",
+            coverage.covered(),
+            coverage.commands().len(),
+        ));
+    }
+    if let Some(condition) = &app.break_condition {
+        label.push_str(&format!(" - break when {condition}"));
+    }
+    if app.selected == SelectItem::Source {
+        if let Some(Some(condition)) = app.breakpoints.get(&app.source_cursor) {
+            label.push_str(&format!(" - breakpoint if {condition}"));
+        }
+    }
+    label
+}
+
+fn memory_label(app: &App) -> String {
+    let mut label = match app.cell_format {
+        CellFormat::Hex => "Memory".to_string(),
+        format => format!("Memory ({})", format.label()),
+    };
+    if app.selected != SelectItem::Memory {
+        return label;
+    }
+    let addr = app.memory_cursor;
+    label.push_str(&format!(" - cell {addr}: {}", app.interpreter.cell(addr)));
+    if let Some(watch) = app.watches.get(&addr) {
+        let mut conditions = Vec::new();
+        if watch.read {
+            conditions.push("read".to_string());
+        }
+        if watch.write {
+            conditions.push("write".to_string());
+        }
+        if let Some(v) = watch.value {
+            conditions.push(format!("= {v}"));
+        }
+        label.push_str(&format!(" (watch: {})", conditions.join(", ")));
+    }
+    label
+}
+
+fn build_hot_loops<'a>(app: &'a App, profile: &Profile) -> Paragraph<'a> {
+    let mut loops = profile.loops.clone();
+    app.loop_order.sort(&mut loops);
+    let header = format!("{:<13} {:>10} {:>6}", "Loop", "Iterations", "Steps");
+    let rows = loops.iter().map(|l| {
+        let ((line, pos), (end_line, end_pos)) = (l.start, l.end);
+        let span = format!("{}:{}-{}:{}", line + 1, pos + 1, end_line + 1, end_pos + 1);
+        let share = profile.share(l) * 100.0;
+        Line::from(format!("{span:<13} {:>10} {share:>5.1}%", l.iterations))
+    });
+    let lines: Vec<Line> = std::iter::once(Line::styled(
+        header,
+        Style::default().add_modifier(Modifier::BOLD),
+    ))
+    .chain(rows)
+    .collect();
+    let label = format!("Hot loops - by {}", app.loop_order.label());
+    Paragraph::new(lines)
+        .style(get_style_base(
+            app,
+            SelectItem::Source,
+            DEFAULT_COLOR,
+            DISABLED_COLOR,
+        ))
+        .block(
+            Block::bordered()
+                .title(label)
+                .padding(Padding::horizontal(1))
+                .style(get_style_base(
+                    app,
+                    SelectItem::Source,
+                    APP_COLOR,
+                    DEFAULT_COLOR,
+                )),
+        )
+}
+
+fn build_log_area(app: &App) -> Paragraph<'_> {
+    let skip = app.dumps.len().saturating_sub(LOG_LINES as usize);
+    let lines: Vec<Line> = app.dumps[skip..]
+        .iter()
+        .map(|d| Line::from(d.to_string()))
+        .collect();
+    let label = match app.dumps.len() {
+        0 => "Log".to_string(),
+        1 => "Log - 1 dump".to_string(),
+        n => format!("Log - {n} dumps"),
+    };
+    Paragraph::new(lines)
+        .style(Style::default().fg(DISABLED_COLOR))
+        .block(
+            Block::bordered()
+                .title(label)
+                .padding(Padding::horizontal(1))
+                .style(Style::default().fg(DEFAULT_COLOR)),
+        )
+}
+
+fn build_error_area(err: &RuntimeError) -> Paragraph<'static> {
+    Paragraph::new(err.to_string())
+        .style(Style::default().fg(ERROR_COLOR))
+        .wrap(Wrap { trim: false })
+        .block(
+            Block::bordered()
+                .title("Error")
+                .padding(Padding::horizontal(1))
+                .style(Style::default().fg(ERROR_COLOR)),
+        )
+}
+
+fn build_diagnostic_area(app: &App) -> Paragraph<'_> {
+    let diagnostics = app.interpreter.diagnostics();
+    let lines: Vec<Line> = diagnostics
+        .iter()
+        .map(|d| {
+            let (line, pos) = d.line_and_pos();
+            Line::raw(format!(
+                "line {}, column {}: {}",
+                line + 1,
+                pos + 1,
+                d.message()
+            ))
+        })
+        .collect();
+    Paragraph::new(lines)
+        .style(Style::default().fg(ERROR_COLOR))
+        .block(
+            Block::bordered()
+                .title(format!("Diagnostics ({})", diagnostics.len()))
+                .padding(Padding::horizontal(1))
+                .style(Style::default().fg(ERROR_COLOR)),
+        )
+}
+
 fn build_button<'a>(app: &'a App, label: &'a str, item: SelectItem) -> Paragraph<'a> {
     Paragraph::new(label)
         .style(get_style_base(app, item, APP_COLOR, DEFAULT_COLOR))
@@ -296,14 +761,19 @@ fn get_style_base(
     not_selected_color: Color,
 ) -> Style {
     match app.state {
-        State::Default | State::Stop => {
+        State::Invalid
+            if matches!(item, SelectItem::Start | SelectItem::Run | SelectItem::Step) =>
+        {
+            Style::default().fg(DISABLED_COLOR)
+        }
+        State::Default | State::Stop | State::Failed | State::Invalid => {
             if app.selected == item {
                 Style::default().fg(selected_color)
             } else {
                 Style::default().fg(not_selected_color)
             }
         }
-        State::Play | State::AutoPlay => {
+        State::Play | State::AutoPlay | State::FastRun => {
             if app.selected == item {
                 Style::default().fg(selected_color)
             } else {
@@ -313,10 +783,54 @@ fn get_style_base(
     }
 }
 
+fn build_status(app: &App) -> Paragraph<'_> {
+    let config = app.interpreter.config();
+    let cell_size = match config.cell_size {
+        CellSize::U8 => "u8",
+        CellSize::U16 => "u16",
+        CellSize::U32 => "u32",
+        CellSize::I8 => "i8",
+        CellSize::I16 => "i16",
+        CellSize::I32 => "i32",
+    };
+    let eof = match config.eof {
+        EofPolicy::Zero => "0",
+        EofPolicy::MinusOne => "-1",
+        EofPolicy::Unchanged => "Unchanged",
+    };
+    let status = [
+        format!("Tape: {}", config.tape_size),
+        format!("Cell: {cell_size}"),
+        format!(
+            "Cell overflow: {}",
+            overflow_policy_label(config.cell_overflow)
+        ),
+        format!(
+            "Pointer overflow: {}",
+            overflow_policy_label(config.pointer_overflow)
+        ),
+        format!("EOF: {eof}"),
+    ];
+    Paragraph::new(status.join(" | "))
+        .style(Style::default().fg(DISABLED_COLOR))
+        .centered()
+}
+
+fn overflow_policy_label(policy: OverflowPolicy) -> &'static str {
+    match policy {
+        OverflowPolicy::Error => "Error",
+        OverflowPolicy::Wrap => "Wrap",
+        OverflowPolicy::Saturate => "Saturate",
+    }
+}
+
 fn build_help(app: &App) -> Paragraph<'_> {
-    let help = help_msg_str(app);
+    let (help, color) = match &app.prompt_error {
+        Some(e) => (e.clone(), ERROR_COLOR),
+        None => (help_msg_str(app), DISABLED_COLOR),
+    };
     Paragraph::new(help)
-        .style(Style::default().fg(DISABLED_COLOR))
+        .style(Style::default().fg(color))
         .block(
             Block::default()
                 .borders(Borders::TOP)
@@ -325,11 +839,27 @@ fn build_help(app: &App) -> Paragraph<'_> {
 }
 
 fn help_msg_str(app: &App) -> String {
+    if app.edit_state.prompt() {
+        return "<Enter> set (empty to clear), <Esc> cancel".to_string();
+    }
+    if app.edit_state == EditState::EnterInputLine {
+        return "<Enter> send the line, <C-d> send EOF, <Esc> cancel".to_string();
+    }
     let mut helps = vec!["<Esc> quit app", "<C-n/C-p> next/prev"];
 
     match app.selected {
         SelectItem::Source => {
-            helps.push("<j/k> scroll");
+            helps.push("<j/k/h/l> move cursor");
+            helps.push("<b> toggle breakpoint");
+            helps.push("<c/C> breakpoint/global condition");
+            match app.source_view {
+                SourceView::Plain => helps.push("<p> show heatmap"),
+                SourceView::Heatmap => {
+                    helps.push("<p> show coverage");
+                    helps.push("<s> sort loops");
+                }
+                SourceView::Coverage => helps.push("<p> hide coverage"),
+            }
         }
         SelectItem::Input => {
             if app.edit_state == EditState::EditInput {
@@ -339,10 +869,39 @@ fn help_msg_str(app: &App) -> String {
                 helps.push("<e> enter editing");
             }
         }
-        SelectItem::Memory => {}
-        SelectItem::Output => {}
+        SelectItem::Memory => {
+            match app.memory_layout {
+                MemoryLayout::Row => {
+                    helps.push("<h/l> move cursor");
+                    helps.push("<g> show grid");
+                }
+                MemoryLayout::Grid16 | MemoryLayout::Grid32 => {
+                    helps.push("<j/k/h/l> move cursor");
+                    helps.push("<+/-> add/remove rows");
+                }
+            }
+            helps.push("<f> change format");
+            if app.ascii_row {
+                helps.push("<a> hide ASCII row");
+            } else {
+                helps.push("<a> show ASCII row");
+            }
+            match app.memory_layout {
+                MemoryLayout::Row => {}
+                MemoryLayout::Grid16 => helps.push("<g> 32 columns"),
+                MemoryLayout::Grid32 => helps.push("<g> hide grid"),
+            }
+            helps.push("<w/r/v> watch write/read/value");
+        }
+        SelectItem::Output => {
+            if app.output_raw {
+                helps.push("<r> show text");
+            } else {
+                helps.push("<r> show raw bytes");
+            }
+        }
         SelectItem::Reset => {
-            if let State::Stop | State::Play | State::AutoPlay = app.state {
+            if app.state != State::Default {
                 helps.push("<Enter> reset");
             }
         }
@@ -352,10 +911,21 @@ fn help_msg_str(app: &App) -> String {
             }
         }
         SelectItem::Pause => {
-            if let State::AutoPlay = app.state {
+            if let State::AutoPlay | State::FastRun = app.state {
                 helps.push("<Enter> pause");
             }
         }
+        SelectItem::Run => {
+            if let State::Default | State::Play | State::AutoPlay = app.state {
+                helps.push("<Enter> run fast to the end or a breakpoint");
+            }
+        }
+        SelectItem::Back => {
+            if let State::Stop | State::Play | State::AutoPlay | State::Failed = app.state {
+                helps.push("<Enter> step back");
+                helps.push("<B> back to breakpoint");
+            }
+        }
         SelectItem::Step => {
             if let State::Default | State::Play | State::AutoPlay = app.state {
                 helps.push("<Enter> step");
@@ -370,7 +940,7 @@ fn help_msg_str(app: &App) -> String {
         State::Default | State::Play if app.edit_state == EditState::None => {
             helps.push("<Space> start");
         }
-        State::AutoPlay => {
+        State::AutoPlay | State::FastRun => {
             helps.push("<Space> pause");
         }
         _ => {}
@@ -1,3 +1,6 @@
+use std::{collections::HashSet, ops::Range};
+
+use itsuki::zero_indexed_enum;
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
@@ -5,24 +8,147 @@ use ratatui::{
     widgets::{Block, BlockExt, Widget},
 };
 
+use crate::{escape, interpreter::config::CellSize};
+
 const DIVIDER: &str = "│";
+/// Candidates for the distance between the addresses shown on the ruler.
+const RULER_STEPS: [usize; 3] = [1, 2, 5];
+/// Minimum width of the ASCII format, for escape sequences such as `\x1b`.
+const ASCII_WIDTH: usize = 4;
+/// Shown in place of values that are not ASCII characters in the ASCII row.
+const NON_ASCII: char = '·';
+
+/// Representation of the cell values.
+#[zero_indexed_enum]
+enum CellFormat {
+    Hex,
+    Dec,
+    SignedDec,
+    Bin,
+    Ascii,
+}
+
+impl CellFormat {
+    pub fn label(&self) -> &'static str {
+        match self {
+            CellFormat::Hex => "hex",
+            CellFormat::Dec => "dec",
+            CellFormat::SignedDec => "signed dec",
+            CellFormat::Bin => "bin",
+            CellFormat::Ascii => "ascii",
+        }
+    }
+
+    /// Returns the number of columns needed to display any value of the cell size.
+    pub fn width(&self, cell_size: CellSize) -> usize {
+        let bits = cell_size.bits();
+        match self {
+            CellFormat::Hex => cell_size.hex_width(),
+            CellFormat::Dec => (u64::MAX >> (64 - bits)).to_string().len(),
+            CellFormat::SignedDec => (-(1i64 << (bits - 1))).to_string().len(),
+            CellFormat::Bin => bits as usize,
+            CellFormat::Ascii => ASCII_WIDTH.max(cell_size.hex_width()),
+        }
+    }
+
+    /// Formats the value of a cell, right-aligned to `width`.
+    ///
+    /// The value is read from its bit pattern, so that the signed and unsigned formats do not
+    /// depend on the cell size. In the ASCII format, control characters are escaped as in the
+    /// Input panel, bytes above `0x7f` are shown as `\xNN`, and larger values in hex.
+    pub fn format(&self, cell_size: CellSize, v: i64) -> String {
+        let bits = cell_size.bits();
+        let w = self.width(cell_size);
+        let pattern = cell_size.bit_pattern(v);
+        match self {
+            CellFormat::Hex => format!("{pattern:>0w$X}"),
+            CellFormat::Dec => format!("{pattern:>w$}"),
+            CellFormat::SignedDec => {
+                let signed = ((pattern << (64 - bits)) as i64) >> (64 - bits);
+                format!("{signed:>w$}")
+            }
+            CellFormat::Bin => format!("{pattern:>0w$b}"),
+            CellFormat::Ascii => match u8::try_from(pattern) {
+                Ok(b) => format!("{:>w$}", escape::escape(&[b])),
+                Err(_) => format!("{pattern:>w$X}"),
+            },
+        }
+    }
+}
+
+/// Shows the value as a single character, with control characters as symbols such as `␊`.
+fn ascii_char(cell_size: CellSize, v: i64) -> char {
+    match cell_size.bit_pattern(v) {
+        b @ 0..=0x7f => escape::preview(&[b as u8])
+            .chars()
+            .next()
+            .unwrap_or(NON_ASCII),
+        _ => NON_ASCII,
+    }
+}
+
+/// Returns the address of the first visible cell, scrolled from `offset` as little as possible so
+/// that the cell at `addr` is visible, and kept within the addresses of the `tape`.
+///
+/// `visible` cells are shown in rows of `align` cells, which start at multiples of `align`.
+pub fn scroll_offset(
+    offset: isize,
+    addr: Option<isize>,
+    tape: Range<isize>,
+    visible: isize,
+    align: isize,
+) -> isize {
+    let row_start = |addr: isize| addr - addr.rem_euclid(align);
+    let mut offset = offset;
+    if let Some(addr) = addr {
+        if addr < offset {
+            offset = row_start(addr);
+        } else if addr >= offset + visible {
+            offset = row_start(addr) + align - visible;
+        }
+    }
+    let last = row_start(tape.end - 1) + align - visible;
+    row_start(offset).min(last).max(row_start(tape.start))
+}
 
 pub struct Memory<'a> {
     block: Option<Block<'a>>,
     style: Style,
     ptr_style: Style,
-    memory: &'a Vec<u8>,
-    current_ptr: Option<u8>,
+    cursor_style: Style,
+    mark_style: Style,
+    ruler_style: Style,
+    memory: &'a [i64],
+    first_address: isize,
+    offset: isize,
+    columns: Option<usize>,
+    cell_size: CellSize,
+    format: CellFormat,
+    ascii_row: bool,
+    current_ptr: Option<usize>,
+    cursor: Option<usize>,
+    marks: HashSet<usize>,
 }
 
 impl<'a> Memory<'a> {
-    pub fn new(memory: &'a Vec<u8>, current_ptr: Option<u8>) -> Memory<'a> {
+    pub fn new(memory: &'a [i64], cell_size: CellSize, current_ptr: Option<usize>) -> Memory<'a> {
         Memory {
             block: None,
             style: Style::default(),
             ptr_style: Style::default(),
+            cursor_style: Style::default(),
+            mark_style: Style::default(),
+            ruler_style: Style::default(),
             memory,
+            first_address: 0,
+            offset: 0,
+            columns: None,
+            cell_size,
+            format: CellFormat::Hex,
+            ascii_row: false,
             current_ptr,
+            cursor: None,
+            marks: HashSet::new(),
         }
     }
 
@@ -40,13 +166,96 @@ impl<'a> Memory<'a> {
         self.ptr_style = style.into();
         self
     }
+
+    /// Highlights the cell at `cursor`.
+    pub fn cursor(mut self, cursor: Option<usize>, style: Style) -> Memory<'a> {
+        self.cursor = cursor;
+        self.cursor_style = style;
+        self
+    }
+
+    pub fn marks<I: IntoIterator<Item = usize>>(mut self, marks: I, style: Style) -> Memory<'a> {
+        self.marks = marks.into_iter().collect();
+        self.mark_style = style;
+        self
+    }
+
+    /// Shows the addresses of the cells, where the first cell is at `first_address`.
+    ///
+    /// The addresses are shown below the cells, or on the left of each row and the column numbers
+    /// above in the grid layout.
+    pub fn ruler(mut self, first_address: isize, style: Style) -> Memory<'a> {
+        self.first_address = first_address;
+        self.ruler_style = style;
+        self
+    }
+
+    /// Starts drawing from the cell at the address `offset`.
+    pub fn offset(mut self, offset: isize) -> Memory<'a> {
+        self.offset = offset;
+        self
+    }
+
+    /// Draws the cells in rows of `columns` cells, as returned by `grid_columns`.
+    pub fn grid(mut self, columns: Option<usize>) -> Memory<'a> {
+        self.columns = columns;
+        self
+    }
+
+    pub fn format(mut self, format: CellFormat) -> Memory<'a> {
+        self.format = format;
+        self
+    }
+
+    /// Shows the values as characters under the cells.
+    pub fn ascii_row(mut self, ascii_row: bool) -> Memory<'a> {
+        self.ascii_row = ascii_row;
+        self
+    }
+
+    /// Returns the number of cells that fit entirely in the width.
+    pub fn visible_cells(&self, width: usize) -> usize {
+        (width + 1) / (self.cell_width() + 1)
+    }
+
+    /// Returns the number of lines used by each row of cells.
+    pub fn row_height(&self) -> usize {
+        1 + usize::from(self.ascii_row)
+    }
+
+    /// Returns the number of columns of the grid that fit in the width, halving `columns` until the
+    /// rows and their addresses fit.
+    pub fn grid_columns(&self, width: usize, columns: usize) -> usize {
+        let address_width = self.address_width() + 1;
+        let fit = self.visible_cells(width.saturating_sub(address_width));
+        let mut columns = columns.max(1);
+        while columns > fit.max(1) {
+            columns /= 2;
+        }
+        columns
+    }
+
+    fn address_width(&self) -> usize {
+        let last = self.first_address + self.memory.len() as isize - 1;
+        self.first_address
+            .to_string()
+            .len()
+            .max(last.to_string().len())
+    }
+
+    fn cell_width(&self) -> usize {
+        self.format.width(self.cell_size)
+    }
 }
 
 impl Widget for Memory<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         self.block.clone().render(area, buf);
         let inner = self.block.inner_if_some(area);
-        self.render_memory(inner, buf);
+        match self.columns {
+            Some(columns) => self.render_grid(inner, buf, columns),
+            None => self.render_memory(inner, buf),
+        }
     }
 }
 
@@ -55,20 +264,345 @@ impl Memory<'_> {
         let x = text_area.x;
         let y = text_area.y;
 
-        let ms = self.memories_str(text_area.width as usize);
-        buf.set_string(x, y, ms, self.style);
+        let w = self.cell_width();
+        let width = text_area.width as usize;
+        let start = usize::try_from(self.offset - self.first_address).unwrap_or(0);
+        let first = self.first_address + start as isize;
+        let cells = self
+            .memory
+            .len()
+            .saturating_sub(start)
+            .min(width.div_ceil(w + 1));
+
+        for ascii in [false, true].into_iter().take(self.row_height()) {
+            let ms: String = self
+                .cells_str(first, cells, ascii)
+                .chars()
+                .take(width)
+                .collect();
+            buf.set_string(x, y + u16::from(ascii), ms, self.style);
+        }
+        let ruler_y = self.row_height() as u16;
+        if text_area.height > ruler_y {
+            let ruler = self.ruler_str(start, width);
+            buf.set_string(x, y + ruler_y, ruler, self.ruler_style);
+        }
+
+        self.highlight_cells(buf, |idx| {
+            let offset = idx.checked_sub(start)? * (w + 1);
+            (offset + w <= text_area.width as usize).then_some((x + offset as u16, y))
+        });
+    }
+
+    fn render_grid(&self, text_area: Rect, buf: &mut Buffer, columns: usize) {
+        let x = text_area.x;
+        let y = text_area.y;
 
+        let w = self.cell_width();
+        let aw = self.address_width();
+        let row_height = self.row_height();
+        let rows = (text_area.height as usize).saturating_sub(1 /* header */) / row_height;
+        let header: String = (0..columns).map(|c| format!("{c:<w$} ")).collect();
+        buf.set_string(x + aw as u16 + 1, y, header.trim_end(), self.ruler_style);
+
+        let end = self.first_address + self.memory.len() as isize;
+        for r in 0..rows {
+            let row = self.offset + (r * columns) as isize;
+            if row >= end {
+                break;
+            }
+            let ry = y + 1 + (r * row_height) as u16;
+            buf.set_string(x, ry, format!("{row:>aw$}"), self.ruler_style);
+            for ascii in [false, true].into_iter().take(row_height) {
+                let cells = self.cells_str(row, columns, ascii);
+                let cells: String = cells.chars().take(columns * (w + 1) - 1).collect();
+                buf.set_string(x + aw as u16 + 1, ry + u16::from(ascii), cells, self.style);
+            }
+        }
+
+        self.highlight_cells(buf, |idx| {
+            let rel = usize::try_from(self.first_address + idx as isize - self.offset).ok()?;
+            let (r, c) = (rel / columns, rel % columns);
+            let cx = x + (aw + 1 + c * (w + 1)) as u16;
+            (r < rows).then_some((cx, y + 1 + (r * row_height) as u16))
+        });
+    }
+
+    /// Highlights the marks, the pointer and the cursor, at the positions given by `position` for
+    /// the indexes of the visible cells, including their characters in the ASCII row.
+    fn highlight_cells(&self, buf: &mut Buffer, position: impl Fn(usize) -> Option<(u16, u16)>) {
+        let (w, h) = (self.cell_width() as u16, self.row_height() as u16);
+        let mut highlight = |idx: usize, style: Style| {
+            if let Some((x, y)) = position(idx) {
+                buf.set_style(Rect::new(x, y, w, h), style);
+            }
+        };
+        for idx in &self.marks {
+            highlight(*idx, self.mark_style);
+        }
         if let Some(cur_ptr) = self.current_ptr {
-            let cur_ptr_area = Rect::new(x + (cur_ptr as u16) * 3, y, 2, 1);
-            buf.set_style(cur_ptr_area, self.ptr_style)
+            highlight(cur_ptr, self.ptr_style);
         }
+        if let Some(cursor) = self.cursor {
+            highlight(cursor, self.cursor_style);
+        }
+    }
+
+    fn index(&self, addr: isize) -> Option<usize> {
+        let idx = usize::try_from(addr - self.first_address).ok()?;
+        (idx < self.memory.len()).then_some(idx)
     }
 
-    fn memories_str(&self, w: usize) -> String {
-        self.memory
-            .iter()
-            .flat_map(|m| format!("{m:>02X}{DIVIDER}").chars().collect::<Vec<_>>())
-            .take(w)
+    /// Formats `count` cells from the address `first`, or their characters for the ASCII row.
+    /// Addresses outside of the tape are left blank.
+    fn cells_str(&self, first: isize, count: usize, ascii: bool) -> String {
+        let w = self.cell_width();
+        let divider = if ascii { " " } else { DIVIDER };
+        (first..first + count as isize)
+            .map(|addr| match self.index(addr) {
+                Some(idx) if ascii => {
+                    let c = ascii_char(self.cell_size, self.memory[idx]);
+                    format!("{c:>w$}{divider}")
+                }
+                Some(idx) => {
+                    let m = self.format.format(self.cell_size, self.memory[idx]);
+                    format!("{m}{divider}")
+                }
+                None => format!("{:w$}{divider}", ""),
+            })
             .collect()
     }
+
+    /// Places the addresses at the first column of their cells, every few cells so that they are
+    /// separated by at least a space.
+    fn ruler_str(&self, start: usize, w: usize) -> String {
+        let cw = self.cell_width() + 1;
+        let cells = self.memory.len().saturating_sub(start).min(w.div_ceil(cw));
+        let first = self.first_address + start as isize;
+        let last = first + cells as isize - 1;
+        let len = first.to_string().len().max(last.to_string().len());
+        let step = (0..)
+            .flat_map(|e| RULER_STEPS.map(|s| s * 10usize.pow(e)))
+            .find(|step| step * cw > len)
+            .unwrap_or(1);
+
+        let mut ruler = String::new();
+        for i in 0..cells {
+            let addr = first + i as isize;
+            let column = i * cw;
+            let label = addr.to_string();
+            let fits = (ruler.is_empty() || ruler.len() < column) && column + label.len() <= w;
+            if addr.rem_euclid(step as isize) == 0 && fits {
+                ruler.push_str(&" ".repeat(column - ruler.len()));
+                ruler.push_str(&label);
+            }
+        }
+        ruler
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::style::{Color, Modifier};
+
+    use super::*;
+
+    fn render_lines(memory: Memory, width: u16, height: u16) -> (Buffer, Vec<String>) {
+        let area = Rect::new(0, 0, width, height);
+        let mut buf = Buffer::empty(area);
+        memory.render(area, &mut buf);
+        let lines = (0..height)
+            .map(|y| {
+                let line: String = (0..width).map(|x| buf[(x, y)].symbol()).collect();
+                line.trim_end().to_string()
+            })
+            .collect();
+        (buf, lines)
+    }
+
+    #[test]
+    fn test_cell_format() {
+        let cases = [
+            (CellFormat::Hex, CellSize::U8, 10, "0A"),
+            (CellFormat::Hex, CellSize::I16, -1, "FFFF"),
+            (CellFormat::Dec, CellSize::U8, 10, " 10"),
+            (CellFormat::Dec, CellSize::I8, -1, "255"),
+            (CellFormat::SignedDec, CellSize::U8, 255, "  -1"),
+            (CellFormat::SignedDec, CellSize::I16, -300, "  -300"),
+            (CellFormat::Bin, CellSize::U8, 5, "00000101"),
+            (CellFormat::Ascii, CellSize::U8, 65, "   A"),
+            (CellFormat::Ascii, CellSize::U8, 10, "  \\n"),
+            (CellFormat::Ascii, CellSize::U8, 0x1b, "\\x1b"),
+            (CellFormat::Ascii, CellSize::U8, 0xff, "\\xff"),
+            (CellFormat::Ascii, CellSize::U16, 0x3042, "3042"),
+            (CellFormat::Ascii, CellSize::U32, 65, "       A"),
+        ];
+        for (format, cell_size, v, expected) in cases {
+            assert_eq!(format.format(cell_size, v), expected, "{format:?} {v}");
+            assert_eq!(format.width(cell_size), expected.len());
+        }
+
+        assert_eq!(ascii_char(CellSize::U8, 65), 'A');
+        assert_eq!(ascii_char(CellSize::U8, 10), '␊');
+        assert_eq!(ascii_char(CellSize::U8, 0x80), NON_ASCII);
+    }
+
+    #[test]
+    fn test_scroll_offset() {
+        // (offset, addr, tape, visible, align, expected)
+        let cases = [
+            // a row of 10 cells follows the pointer by as little as possible
+            (0, Some(5), 0..30000, 10, 1, 0),
+            (0, Some(10), 0..30000, 10, 1, 1),
+            (0, Some(25), 0..30000, 10, 1, 16),
+            (20, Some(5), 0..30000, 10, 1, 5),
+            (20, None, 0..30000, 10, 1, 20),
+            // clamped at the ends of the tape
+            (-3, None, 0..30000, 10, 1, 0),
+            (29995, None, 0..30000, 10, 1, 29990),
+            (0, Some(29999), 0..30000, 10, 1, 29990),
+            (0, None, 0..4, 10, 1, 0),
+            // and at the edges of the cells allocated so far on an infinite tape
+            (0, Some(-8), -8..24, 10, 1, -8),
+            (20, None, -8..24, 10, 1, 14),
+            // 4 rows of 16 cells, starting at multiples of 16
+            (0, Some(70), 0..30000, 64, 16, 16),
+            (64, Some(5), 0..30000, 64, 16, 0),
+            (5, None, 0..30000, 64, 16, 0),
+            (0, Some(29999), 0..30000, 64, 16, 29936),
+            (0, Some(-8), -8..40, 32, 16, -16),
+            (100, None, -8..40, 32, 16, 16),
+        ];
+        for (offset, addr, tape, visible, align, expected) in cases {
+            let msg = format!("{offset} {addr:?} {tape:?}");
+            assert_eq!(
+                scroll_offset(offset, addr, tape, visible, align),
+                expected,
+                "{msg}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ruler_str() {
+        let tape = vec![0; 100010];
+        // (format, first address, index of the first visible cell, width, expected)
+        let cases = [
+            // every address fits in a 3-column hex cell
+            (CellFormat::Hex, 0, 0, 30, "0  1  2  3  4  5  6  7  8  9"),
+            // wider addresses are shown every 2 or 5 cells to keep a space between them
+            (CellFormat::Hex, 0, 100, 30, "100   102   104   106   108"),
+            (
+                CellFormat::Hex,
+                0,
+                10000,
+                30,
+                "10000 10002 10004 10006 10008",
+            ),
+            (CellFormat::Hex, 0, 100000, 30, "100000         100005"),
+            // wider cells leave room for every address
+            (
+                CellFormat::Dec,
+                0,
+                100,
+                40,
+                "100 101 102 103 104 105 106 107 108 109",
+            ),
+            (
+                CellFormat::SignedDec,
+                0,
+                10000,
+                25,
+                "10000     10002     10004",
+            ),
+            (
+                CellFormat::Bin,
+                0,
+                10000,
+                45,
+                "10000    10001    10002    10003    10004",
+            ),
+            // negative addresses of an infinite tape, and a label cut by the width is dropped
+            (CellFormat::Hex, -20, 0, 15, "-20   -18   -16"),
+            (CellFormat::Hex, -20, 0, 14, "-20   -18"),
+        ];
+        for (format, first_address, start, width, expected) in cases {
+            let memory = Memory::new(&tape, CellSize::U8, None)
+                .format(format)
+                .ruler(first_address, Style::default());
+            let msg = format!("{format:?} {first_address} {start}");
+            assert_eq!(memory.ruler_str(start, width), expected, "{msg}");
+        }
+    }
+
+    #[test]
+    fn test_grid_columns() {
+        let tape = vec![0; 30000];
+        let memory = Memory::new(&tape, CellSize::U8, None);
+        // 5 columns for the addresses and a space, then 3 columns per cell without the last divider
+        assert_eq!(memory.grid_columns(101, 32), 32);
+        assert_eq!(memory.grid_columns(100, 32), 16);
+        assert_eq!(memory.grid_columns(80, 16), 16);
+        assert_eq!(memory.grid_columns(50, 16), 8);
+        assert_eq!(memory.grid_columns(10, 16), 1);
+        assert_eq!(memory.grid_columns(0, 16), 1);
+
+        let memory = Memory::new(&tape, CellSize::U8, None).format(CellFormat::Dec);
+        assert_eq!(memory.grid_columns(70, 32), 16);
+
+        // negative addresses of an infinite tape take their sign into account
+        let memory = Memory::new(&tape, CellSize::U8, None).ruler(-15000, Style::default());
+        assert_eq!(memory.grid_columns(102, 32), 32);
+        assert_eq!(memory.grid_columns(101, 32), 16);
+    }
+
+    #[test]
+    fn test_render_grid() {
+        let tape: Vec<i64> = (0..10).collect();
+        let ptr_style = Style::default().fg(Color::Red);
+        let mark_style = Style::default().bg(Color::Blue);
+        let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+        let memory = Memory::new(&tape, CellSize::U8, Some(9))
+            .grid(Some(4))
+            .ptr_style(ptr_style)
+            .marks([5], mark_style)
+            .cursor(Some(2), cursor_style);
+        let (buf, lines) = render_lines(memory, 20, 5);
+        assert_eq!(
+            lines,
+            [
+                "  0  1  2  3",
+                "0 00│01│02│03",
+                "4 04│05│06│07",
+                "8 08│09│  │",
+                "",
+            ]
+        );
+
+        // the highlights follow the cells onto the later rows
+        let style_at = |x, y| buf[(x, y)].style();
+        assert_eq!(style_at(5, 3).fg, Some(Color::Red));
+        assert_eq!(style_at(6, 3).fg, Some(Color::Red));
+        assert_eq!(style_at(2, 3).fg, Some(Color::Reset));
+        assert_eq!(style_at(5, 2).bg, Some(Color::Blue));
+        assert_eq!(style_at(6, 2).bg, Some(Color::Blue));
+        assert!(style_at(8, 1).add_modifier.contains(Modifier::REVERSED));
+        assert!(!style_at(5, 1).add_modifier.contains(Modifier::REVERSED));
+
+        // starting from the second row, with the ASCII row under each row of cells
+        let tape = [0x41, 0x42, 0x43, 0x0a, 0x44, 0x45];
+        let memory = Memory::new(&tape, CellSize::U8, Some(4))
+            .grid(Some(2))
+            .offset(2)
+            .ascii_row(true)
+            .ptr_style(ptr_style);
+        let (buf, lines) = render_lines(memory, 10, 5);
+        assert_eq!(
+            lines,
+            ["  0  1", "2 43│0A", "   C  ␊", "4 44│45", "   D  E"]
+        );
+        assert_eq!(buf[(2, 3)].style().fg, Some(Color::Red));
+        assert_eq!(buf[(3, 4)].style().fg, Some(Color::Red));
+        assert_eq!(buf[(2, 1)].style().fg, Some(Color::Reset));
+    }
 }